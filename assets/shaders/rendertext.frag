@@ -1,13 +1,14 @@
 #version 330 core
 
 in vec2 TexCoord;
+in vec4 GlyphColor;
 
 out vec4 Color;
 
-uniform sampler2D tex;
+// Glyph coverage in the red channel
+uniform sampler2D atlas;
 
 void main()
 {
-    Color = texture(tex, TexCoord);
-    // Color =texture(texture, TexCoord) + vec4(1.0,0.0,0.0,1.0);
-}
\ No newline at end of file
+    Color = vec4(GlyphColor.rgb, GlyphColor.a * texture(atlas, TexCoord).r);
+}
@@ -1,13 +1,22 @@
 #version 330 core
 
-layout (location = 0) in vec3 pos;
+layout (location = 0) in vec2 pos;
 layout (location = 1) in vec2 tex;
+layout (location = 2) in vec4 clr;
 
 out vec2 TexCoord;
+out vec4 GlyphColor;
+
+// Window size in pixels, positions are in pixels from the top left corner
+uniform vec2 screen_size;
+// Padding between the window edges and the text
+uniform vec2 offset;
 
 void main()
 {
-    gl_Position = vec4(pos, 1.0);
-    
+    vec2 position = pos + offset;
+    gl_Position = vec4(position.x / screen_size.x * 2.0 - 1.0, 1.0 - position.y / screen_size.y * 2.0, 0.0, 1.0);
+
     TexCoord = tex;
-}
\ No newline at end of file
+    GlyphColor = clr;
+}
@@ -0,0 +1,355 @@
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The documented default configuration, also shipped in the assets directory
+pub const DEFAULT_CONFIG: &str = include_str!("../assets/config.toml");
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to read config file {path}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        inner: io::Error,
+    },
+    #[error("Invalid config file {path}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        inner: toml::de::Error,
+    },
+    #[error("Invalid option `{option}`: {message}")]
+    Option { option: String, message: String },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub font: Font,
+    pub colors: Colors,
+    pub window: Window,
+    pub cursor: Cursor,
+    pub behavior: Behavior,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Font {
+    /// Relative to the assets directory unless absolute
+    pub path: String,
+    pub size: f32,
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Font { path: "fonts/Modeseven.ttf".to_string(), size: 30.0 }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colors {
+    pub scheme: Scheme,
+    /// Replace the colors of the scheme
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<Rgb>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<Rgb>,
+    /// Replaces the first of the 16 base colors of the scheme
+    pub palette: Vec<Rgb>,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            scheme: Scheme::GreenPhosphor,
+            foreground: None,
+            background: None,
+            palette: vec![],
+        }
+    }
+}
+
+/// Built-in color schemes
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scheme {
+    GreenPhosphor,
+    Amber,
+    SolarizedDark,
+    SolarizedLight,
+    Xterm,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Window {
+    pub width: u32,
+    pub height: u32,
+    /// Pixels between the edges of the window and the text
+    pub padding: u32,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Window { width: 1024, height: 768, padding: 0 }
+    }
+}
+
+/// Shape and blink timings in milliseconds
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Cursor {
+    pub style: CursorStyle,
+    pub blink_period: u64,
+    pub blink_visible: u64,
+    pub blink_after_typing: u64,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Cursor {
+            style: CursorStyle::Block,
+            blink_period: 1000,
+            blink_visible: 400,
+            blink_after_typing: 500,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorStyle {
+    Block,
+    Underline,
+    Beam,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Behavior {
+    pub scrollback: usize,
+    /// Program to run instead of `$SHELL`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+}
+
+impl Default for Behavior {
+    fn default() -> Self {
+        Behavior { scrollback: crate::terminal::DEFAULT_SCROLLBACK, shell: None }
+    }
+}
+
+/// Color written as `"#rrggbb"`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub fn rgba(&self) -> (u8, u8, u8, u8) {
+        (self.0, self.1, self.2, 255)
+    }
+
+    /// Scales the brightness, `factor` below 1 darkens
+    pub fn scale(&self, factor: f32) -> Rgb {
+        let channel = |c: u8| (c as f32 * factor).round().clamp(0.0, 255.0) as u8;
+        Rgb(channel(self.0), channel(self.1), channel(self.2))
+    }
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid color `{}`, expected \"#rrggbb\"", value);
+        let hex = value.strip_prefix('#').filter(|hex| hex.len() == 6).ok_or_else(invalid)?;
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        Ok(Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl From<Rgb> for String {
+    fn from(rgb: Rgb) -> Self {
+        rgb.to_string()
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/terminal-emulator/config.toml`, falling back to `~/.config`
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_home.join("terminal-emulator").join("config.toml"))
+    }
+
+    /// Reads the config file at `path`, or at the default path if there is one, and applies
+    /// `key=value` options on top of it
+    pub fn load(path: Option<&Path>, options: &[String]) -> Result<Config, Error> {
+        let config = match path {
+            Some(path) => Config::from_file(path)?,
+            None => match Config::default_path() {
+                Some(path) if path.exists() => Config::from_file(&path)?,
+                _ => Config::default(),
+            },
+        };
+        config.with_options(options)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, Error> {
+        let text = fs::read_to_string(path)
+            .map_err(|inner| Error::Read { path: path.to_path_buf(), inner })?;
+        toml::from_str(&text).map_err(|inner| Error::Parse { path: path.to_path_buf(), inner })
+    }
+
+    /// Overrides keys given as `section.key=value`, values are TOML and taken as a string
+    /// when they do not parse
+    pub fn with_options(self, options: &[String]) -> Result<Config, Error> {
+        if options.is_empty() {
+            return Ok(self);
+        }
+        let error =
+            |option: &str, message: String| Error::Option { option: option.into(), message };
+
+        let mut table = toml::Value::try_from(self).expect("config serializes to a table");
+        for option in options {
+            let (key, value) = option
+                .split_once('=')
+                .ok_or_else(|| error(option, "expected `section.key=value`".into()))?;
+            let value = toml::from_str::<toml::value::Table>(&format!("value = {}", value))
+                .ok()
+                .and_then(|mut parsed| parsed.remove("value"))
+                .unwrap_or_else(|| toml::Value::String(value.to_string()));
+
+            let mut target = &mut table;
+            for part in key.trim().split('.') {
+                let section = target
+                    .as_table_mut()
+                    .ok_or_else(|| error(option, format!("`{}` is not a section", key)))?;
+                target =
+                    section.entry(part).or_insert_with(|| toml::Value::Table(Default::default()));
+            }
+            *target = value;
+        }
+        table
+            .try_into()
+            .map_err(|inner: toml::de::Error| error(&options.join(" "), inner.to_string()))
+    }
+}
+
+/// Notices changes of the config file by polling its modification time
+pub struct Watcher {
+    /// `None` watches the default path
+    path: Option<PathBuf>,
+    options: Vec<String>,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl Watcher {
+    const INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Watches the file `Config::load` was called with, reloads apply the same options
+    pub fn new(path: Option<PathBuf>, options: Vec<String>) -> Watcher {
+        let mut watcher = Watcher { path, options, modified: None, last_check: Instant::now() };
+        watcher.modified = watcher.modified();
+        watcher
+    }
+
+    /// The config loaded again if the file changed since the last call
+    pub fn poll(&mut self) -> Option<Result<Config, Error>> {
+        if self.last_check.elapsed() < Watcher::INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let modified = self.modified();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(Config::load(self.path.as_deref(), &self.options))
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        let path = self.path.clone().or_else(Config::default_path)?;
+        fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documented_default_matches_default() {
+        let config: Config = toml::from_str(DEFAULT_CONFIG).unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn missing_keys_keep_defaults() {
+        let config: Config = toml::from_str("[font]\nsize = 18.5").unwrap();
+        assert_eq!(config.font.size, 18.5);
+        assert_eq!(config.font.path, Font::default().path);
+        assert_eq!(config.window, Window::default());
+    }
+
+    #[test]
+    fn unknown_keys_are_reported() {
+        let error = toml::from_str::<Config>("[font]\nsise = 18").unwrap_err().to_string();
+        assert!(error.contains("unknown field `sise`"), "{}", error);
+        assert!(error.contains("for key `font`"), "{}", error);
+    }
+
+    #[test]
+    fn colors_must_be_hex() {
+        let config: Config = toml::from_str("[colors]\nforeground = \"#FF8000\"").unwrap();
+        assert_eq!(config.colors.foreground, Some(Rgb(255, 128, 0)));
+        let error = toml::from_str::<Config>("[colors]\nforeground = \"green\"").unwrap_err();
+        assert!(error.to_string().contains("invalid color `green`"), "{}", error);
+    }
+
+    #[test]
+    fn schemes_are_named_in_kebab_case() {
+        let config: Config = toml::from_str("[colors]\nscheme = \"solarized-dark\"").unwrap();
+        assert_eq!(config.colors.scheme, Scheme::SolarizedDark);
+        let error = toml::from_str::<Config>("[colors]\nscheme = \"neon\"").unwrap_err();
+        assert!(error.to_string().contains("unknown variant `neon`"), "{}", error);
+    }
+
+    #[test]
+    fn options_override_any_key() {
+        let options = [
+            "font.size=24".to_string(),
+            "colors.foreground=#ffffff".to_string(),
+            "colors.scheme=amber".to_string(),
+            "behavior.shell=/bin/zsh".to_string(),
+            "window.width=640".to_string(),
+        ];
+        let config = Config::default().with_options(&options).unwrap();
+        assert_eq!(config.font.size, 24.0);
+        assert_eq!(config.colors.scheme, Scheme::Amber);
+        assert_eq!(config.colors.foreground, Some(Rgb(255, 255, 255)));
+        assert_eq!(config.behavior.shell.as_deref(), Some("/bin/zsh"));
+        assert_eq!(config.window.width, 640);
+
+        let error = Config::default().with_options(&["font.sise=1".to_string()]).unwrap_err();
+        assert!(error.to_string().contains("unknown field `sise`"), "{}", error);
+        let error = Config::default().with_options(&["font.size".to_string()]).unwrap_err();
+        assert!(error.to_string().contains("section.key=value"), "{}", error);
+    }
+}
@@ -1,2 +1,3 @@
+pub mod atlas;
 pub mod cursor;
 pub mod rendertext;
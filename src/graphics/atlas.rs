@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use rusttype::{point, Font, GlyphId, Scale};
+
+use crate::render_gl::texture::Texture2D;
+
+const ATLAS_SIZE: u32 = 1024;
+/// Empty pixels around each glyph so linear filtering does not bleed neighbours in
+const PADDING: u32 = 1;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Style {
+    Regular,
+    /// The font has no bold face, bold is synthesized by drawing the glyph twice a pixel apart
+    Bold,
+}
+
+/// What a rasterized glyph is cached by
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GlyphKey {
+    pub id: GlyphId,
+    /// Font scale in pixels as the bits of an `f32`, which can be hashed
+    pub size: u32,
+    pub style: Style,
+}
+
+/// Place of a glyph in the atlas
+#[derive(Copy, Clone, Debug)]
+pub struct AtlasGlyph {
+    /// Texture coordinates of the top left and bottom right corners
+    pub uv_min: (f32, f32),
+    pub uv_max: (f32, f32),
+    pub width: f32,
+    pub height: f32,
+    /// Position of the top left corner relative to the top left of the cell
+    pub left: f32,
+    pub top: f32,
+}
+
+/// Single channel texture where glyphs are rasterized once and packed on shelves
+pub struct GlyphAtlas {
+    texture: Texture2D,
+    /// `None` for glyphs without pixels, like space
+    glyphs: HashMap<GlyphKey, Option<AtlasGlyph>>,
+    shelf_x: u32,
+    shelf_y: u32,
+    shelf_height: u32,
+    /// Bumped when the atlas is cleared, previously returned glyphs are no longer valid
+    generation: usize,
+    /// Texture coordinates of an opaque pixel, for drawing solid rectangles
+    solid: (f32, f32),
+}
+
+impl GlyphAtlas {
+    pub fn new(gl: &gl::Gl) -> GlyphAtlas {
+        let texture = Texture2D::new_red(gl, ATLAS_SIZE, ATLAS_SIZE);
+        texture.bind();
+        texture.generate();
+        texture.unbind();
+
+        let mut atlas = GlyphAtlas {
+            texture,
+            glyphs: HashMap::new(),
+            shelf_x: 0,
+            shelf_y: 0,
+            shelf_height: 0,
+            generation: 0,
+            solid: (0.0, 0.0),
+        };
+        atlas.add_solid();
+        atlas
+    }
+
+    pub fn texture(&self) -> &Texture2D {
+        &self.texture
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn solid(&self) -> (f32, f32) {
+        self.solid
+    }
+
+    /// Looks up a glyph, rasterizing it on first use. Starts over with an empty atlas
+    /// when it is full
+    pub fn get(&mut self, font: &Font, key: GlyphKey, ascent: f32) -> Option<AtlasGlyph> {
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return *glyph;
+        }
+
+        let glyph = font
+            .glyph(key.id)
+            .scaled(Scale::uniform(f32::from_bits(key.size)))
+            .positioned(point(0.0, ascent));
+        let bb = match glyph.pixel_bounding_box() {
+            Some(bb) => bb,
+            None => {
+                self.glyphs.insert(key, None);
+                return None;
+            }
+        };
+
+        let bold = (key.style == Style::Bold) as u32;
+        let width = bb.width() as u32 + bold;
+        let height = bb.height() as u32;
+        let mut pixels = vec![0u8; (width * height) as usize];
+        glyph.draw(|x, y, v| {
+            let value = (v * 255.0) as u8;
+            for x in x..=x + bold {
+                let pixel = &mut pixels[(x + y * width) as usize];
+                *pixel = (*pixel).max(value);
+            }
+        });
+
+        let (x, y) = match self.allocate(width, height) {
+            Some(position) => position,
+            None => {
+                self.clear();
+                self.allocate(width, height)?
+            }
+        };
+        self.texture.bind();
+        self.texture.update_region(x, y, width, height, &pixels);
+        self.texture.unbind();
+
+        let size = ATLAS_SIZE as f32;
+        let atlas_glyph = AtlasGlyph {
+            uv_min: (x as f32 / size, y as f32 / size),
+            uv_max: ((x + width) as f32 / size, (y + height) as f32 / size),
+            width: width as f32,
+            height: height as f32,
+            left: bb.min.x as f32,
+            top: bb.min.y as f32,
+        };
+        self.glyphs.insert(key, Some(atlas_glyph));
+        Some(atlas_glyph)
+    }
+
+    /// Finds room for a `width` by `height` glyph, opening a new shelf when the current one is full
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width + PADDING > ATLAS_SIZE {
+            return None;
+        }
+        if self.shelf_x + width + PADDING > ATLAS_SIZE {
+            self.shelf_y += self.shelf_height + PADDING;
+            self.shelf_x = 0;
+            self.shelf_height = 0;
+        }
+        if self.shelf_y + height + PADDING > ATLAS_SIZE {
+            return None;
+        }
+
+        let position = (self.shelf_x + PADDING, self.shelf_y + PADDING);
+        self.shelf_x += width + PADDING;
+        self.shelf_height = self.shelf_height.max(height);
+        Some(position)
+    }
+
+    fn clear(&mut self) {
+        self.glyphs.clear();
+        self.shelf_x = 0;
+        self.shelf_y = 0;
+        self.shelf_height = 0;
+        self.generation += 1;
+        // Wipe the old pixels so they do not show through the padding of new glyphs
+        self.texture.bind();
+        self.texture.generate();
+        self.texture.unbind();
+        self.add_solid();
+    }
+
+    /// Reserves a block of opaque pixels, sampled in its middle so filtering keeps it opaque
+    fn add_solid(&mut self) {
+        let (x, y) = self.allocate(3, 3).expect("an empty atlas has room");
+        self.texture.bind();
+        self.texture.update_region(x, y, 3, 3, &[255; 9]);
+        self.texture.unbind();
+        let size = ATLAS_SIZE as f32;
+        self.solid = ((x as f32 + 1.5) / size, (y as f32 + 1.5) / size);
+    }
+}
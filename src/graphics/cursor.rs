@@ -3,6 +3,7 @@ use nalgebra::{Matrix4, Vector3};
 use render_gl_derive::VertexAttribPointers;
 
 use crate::{
+    config::CursorStyle,
     render_gl::{
         self,
         buffer::{self, ArrayBuffer, VertexArray},
@@ -26,18 +27,19 @@ pub struct Cursor {
     _vbo: buffer::ArrayBuffer, // _ to disable warning about not used vbo
     vao: buffer::VertexArray,
 
-    x: u32,
-    y: u32,
     width: f32,
     height: f32,
-    field_width: f32,
-    field_height: f32,
+    screen_width: f32,
+    screen_height: f32,
+    padding: f32,
+    style: CursorStyle,
 
-    to_screen: Matrix4<f32>,
     model: Matrix4<f32>,
 }
 
 impl Cursor {
+    /// `width` and `height` are the size of a cell, all sizes are in pixels
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         res: &Resources,
         gl: &gl::Gl,
@@ -45,6 +47,8 @@ impl Cursor {
         height: f32,
         screen_width: f32,
         screen_height: f32,
+        padding: f32,
+        style: CursorStyle,
         color: (u8, u8, u8, u8),
     ) -> Result<Cursor, anyhow::Error> {
         let program = render_gl::Program::from_res(gl, res, "shaders/cursor")?;
@@ -79,66 +83,57 @@ impl Cursor {
         vbo.unbind();
         vao.unbind();
 
-        let field_width = screen_width / width;
-        let field_height = screen_height / height;
-        let to_screen: Matrix4<f32> = Matrix4::identity()
-            .append_nonuniform_scaling(&Vector3::new(
-                1. / (field_width / 2.),
-                1. / (field_height / 2.),
-                1.,
-            ))
-            .append_translation(&Vector3::new(-1.0, -1.0, 0.0))
-            .append_nonuniform_scaling(&Vector3::new(1.0, -1.0, 0.0));
-
-        Ok(Cursor {
+        let mut cursor = Cursor {
             gl: gl.clone(),
             program,
             _vbo: vbo,
             vao,
             width,
             height,
-            field_width,
-            field_height,
-            to_screen,
+            screen_width,
+            screen_height,
+            padding,
+            style,
             model: Matrix4::identity(),
-            x: 0,
-            y: 0,
-        })
+        };
+        cursor.move_to(0, 0, 1);
+        Ok(cursor)
     }
 
-    pub fn move_to(&mut self, x: u32, y: u32) {
-        self.x = x;
-        self.y = y;
-        self.model = Matrix4::new_translation(&Vector3::new(x as f32, y as f32, 0.0));
+    /// Moves the cursor to a cell, `columns` is 2 over wide characters
+    pub fn move_to(&mut self, x: u32, y: u32, columns: u32) {
+        let cell_width = self.width * columns as f32;
+        // Offset and size of the shape inside the cell
+        let (left, top, width, height) = match self.style {
+            CursorStyle::Block => (0.0, 0.0, cell_width, self.height),
+            CursorStyle::Underline => {
+                let thickness = (self.height * 0.1).max(2.0);
+                (0.0, self.height - thickness, cell_width, thickness)
+            }
+            CursorStyle::Beam => (0.0, 0.0, (self.width * 0.15).max(2.0), self.height),
+        };
+        let x = self.padding + x as f32 * self.width + left;
+        let y = self.padding + y as f32 * self.height + top;
+        self.model = Matrix4::new_translation(&Vector3::new(x, y, 0.0))
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(width, height, 1.0));
     }
 
     pub fn update_size(&mut self, screen_width: f32, screen_height: f32) {
-        let new_field_width = screen_width / self.width;
-        let new_field_height = screen_height / self.height;
-        self.to_screen.prepend_translation_mut(&Vector3::new(
-            self.field_width,
-            -self.field_height,
-            0.0,
-        ));
-        self.to_screen.prepend_nonuniform_scaling_mut(&Vector3::new(
-            self.field_width as f32 / new_field_width,
-            self.field_height as f32 / new_field_height,
-            1.0,
-        ));
-
-        self.field_width = new_field_width;
-        self.field_height = new_field_height;
-        self.to_screen.prepend_translation_mut(&Vector3::new(
-            -self.field_width,
-            self.field_height,
-            0.0,
-        ));
+        self.screen_width = screen_width;
+        self.screen_height = screen_height;
     }
 
     pub fn render(&mut self) {
         self.program.set_used();
 
-        self.program.set_matrix("to_screen", self.to_screen);
+        // Pixels from the top left corner to normalized device coordinates
+        let to_screen = Matrix4::new_translation(&Vector3::new(-1.0, 1.0, 0.0))
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(
+                2.0 / self.screen_width,
+                -2.0 / self.screen_height,
+                1.0,
+            ));
+        self.program.set_matrix("to_screen", to_screen);
         self.program.set_matrix("model", self.model);
         self.vao.bind();
         unsafe {
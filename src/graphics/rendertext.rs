@@ -1,38 +1,102 @@
-use anyhow::Ok;
+use anyhow::{Context, Ok};
 use render_gl_derive::VertexAttribPointers;
-use rusttype::{point, Font, Point, Scale};
+use rusttype::{point, Font, Scale};
 
 use crate::{
+    config,
+    graphics::atlas::{GlyphAtlas, GlyphKey, Style},
     render_gl::{
         self,
         buffer::{self, ArrayBuffer, VertexArray},
         data,
-        texture::Texture2D,
     },
     resources::Resources,
 };
 
+/// Combining marks drawn over the character of a cell, further ones are dropped
+pub const MARKS_PER_CELL: usize = 2;
+/// Quad of the character and its marks, backgrounds are drawn before all of them
+const GLYPH_VERTICES: usize = 6 * (1 + MARKS_PER_CELL);
+/// Times the screen is built while the atlas keeps filling up during a rebuild
+const REBUILD_ATTEMPTS: usize = 3;
+
 #[derive(VertexAttribPointers, Copy, Clone, Debug)]
 #[repr(C, packed)]
 struct Vertex {
     #[location = 0]
-    pos: data::f32_f32_f32,
+    pos: data::f32_f32,
     #[location = 1]
     tex: data::f32_f32,
+    #[location = 2]
+    clr: data::u2_u10_u10_u10_rev_float,
+}
+
+/// Two triangles covering the rectangle from `min` to `max` in pixels
+fn quad(
+    min: (f32, f32),
+    max: (f32, f32),
+    uv_min: (f32, f32),
+    uv_max: (f32, f32),
+    color: (u8, u8, u8, u8),
+) -> [Vertex; 6] {
+    let (r, g, b, a) = color;
+    let clr = (r as f32 / 255., g as f32 / 255., b as f32 / 255., a as f32 / 255.);
+    let vertex = |x: f32, y: f32, u: f32, v: f32| Vertex {
+        pos: (x, y).into(),
+        tex: (u, v).into(),
+        clr: clr.into(),
+    };
+    let ((x0, y0), (x1, y1)) = (min, max);
+    let ((u0, v0), (u1, v1)) = (uv_min, uv_max);
+    [
+        vertex(x0, y0, u0, v0),
+        vertex(x1, y0, u1, v0),
+        vertex(x1, y1, u1, v1),
+        vertex(x1, y1, u1, v1),
+        vertex(x0, y1, u0, v1),
+        vertex(x0, y0, u0, v0),
+    ]
 }
 
+impl Vertex {
+    /// Degenerate quad for cells without a visible glyph
+    fn empty() -> Vertex {
+        Vertex { pos: (0.0, 0.0).into(), tex: (0.0, 0.0).into(), clr: (0.0, 0.0, 0.0, 0.0).into() }
+    }
+}
+
+/// What is drawn in one cell of the screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GlyphCell {
+    pub c: char,
+    pub marks: [Option<char>; MARKS_PER_CELL],
+    pub fg: (u8, u8, u8, u8),
+    /// `None` leaves the window background
+    pub bg: Option<(u8, u8, u8, u8)>,
+    pub bold: bool,
+}
+
+/// Draws the screen as a quad per cell sampling glyphs from an atlas, only cells that
+/// changed since the last update are uploaded. The vertices hold the background quads
+/// of all cells followed by their glyphs, so glyphs reaching into the next cell are not
+/// covered by its background
 pub struct RenderText {
-    gl: gl::Gl,
     program: render_gl::Program,
-    _vbo: buffer::ArrayBuffer,
+    vbo: buffer::ArrayBuffer,
     vao: buffer::VertexArray,
-    texture: Texture2D,
+    atlas: GlyphAtlas,
     font: Font<'static>,
     scale: Scale,
+    ascent: f32,
     pub glyph_width: u32,
     pub glyph_height: u32,
-    offset: Point<f32>,
-    color: (u8, u8, u8, u8),
+    screen_size: (f32, f32),
+    padding: f32,
+    cols: usize,
+    cells: Vec<GlyphCell>,
+    vertices: Vec<Vertex>,
+    atlas_generation: usize,
+    gl: gl::Gl,
 }
 
 impl RenderText {
@@ -41,40 +105,23 @@ impl RenderText {
         gl: &gl::Gl,
         width: u32,
         height: u32,
-        color: (u8, u8, u8, u8),
+        font: &config::Font,
+        padding: u32,
     ) -> Result<RenderText, anyhow::Error> {
         let program = render_gl::Program::from_res(gl, res, "shaders/rendertext")?;
 
-        #[rustfmt::skip]
-        let vertices: Vec<Vertex> = vec![
-            Vertex { pos: (-1.0, -1.0, 0.0).into(), tex: (0.0, 0.0).into() }, // bottom left
-            Vertex { pos: (1.0, -1.0, 0.0).into(), tex: (1.0, 0.0).into() },  // bottom right
-            Vertex { pos: (1.0, 1.0, 0.0).into(), tex: (1.0, 1.0).into() },   // top right
-            Vertex { pos: (1.0, 1.0, 0.0).into(), tex: (1.0, 1.0).into() },   // top right
-            Vertex { pos: (-1.0, 1.0, 0.0).into(), tex: (0.0, 1.0).into() },  // top left
-            Vertex { pos: (-1.0, -1.0, 0.0).into(), tex: (0.0, 0.0).into() }, // bottom left
-        ];
-
-        let texture = Texture2D::new_rgba(gl, width, height);
-
-        texture.bind();
-        texture.generate();
-
         let vbo = ArrayBuffer::new(gl);
-        vbo.bind();
-        vbo.static_draw_data(&vertices);
-        vbo.unbind();
-
         let vao = VertexArray::new(gl);
         vao.bind();
         vbo.bind();
         Vertex::vertex_attrib_pointers(gl);
         vbo.unbind();
         vao.unbind();
-        texture.unbind();
 
-        let font = res.load_font("fonts/Modeseven.ttf")?;
-        let scale = Scale::uniform(30.0);
+        let scale = Scale::uniform(font.size);
+        let font = res
+            .load_font(&font.path)
+            .with_context(|| format!("Failed to load font {}", font.path))?;
         let v_metrics = font.v_metrics(scale);
         let glyph_width = font
             .layout("a", scale, point(0.0, 0.0))
@@ -82,90 +129,160 @@ impl RenderText {
             .last()
             .unwrap_or(0.0) as u32;
         let glyph_height = (v_metrics.ascent - v_metrics.descent).ceil() as u32;
-        let offset = point(0.0, v_metrics.ascent);
 
         Ok(RenderText {
-            gl: gl.clone(),
             program,
-            _vbo: vbo,
+            vbo,
             vao,
-            texture,
+            atlas: GlyphAtlas::new(gl),
             font,
             scale,
+            ascent: v_metrics.ascent,
             glyph_width,
             glyph_height,
-            offset,
-            color,
+            screen_size: (width as f32, height as f32),
+            padding: padding as f32,
+            cols: 0,
+            cells: vec![],
+            vertices: vec![],
+            atlas_generation: 0,
+            gl: gl.clone(),
         })
     }
 
-    fn render_text(&mut self, text: &str, line_no: usize) {
-        let glyphs: Vec<_> = self.font.layout(text, self.scale, self.offset).collect();
-
-        let width = self.texture.image_res.width;
-        let height = self.texture.image_res.height;
-
-        for g in glyphs {
-            // if g.id() == rusttype::GlyphId(0) {
-            //     println!("{:?}", g);
-            //     continue;
-            // }
-            if let Some(bb) = g.pixel_bounding_box() {
-                g.draw(|x, y, v| {
-                    // println!("{:?}", g);
-                    let x = x + bb.min.x as u32;
-                    if x >= width {
-                        return;
-                    }
-
-                    let y = height.saturating_sub(y + bb.min.y as u32);
-                    let y = y.saturating_sub(line_no as u32 * self.glyph_height);
-
-                    let idx = (4 * (x + y * width)) as usize;
-                    // if idx >= self.texture.image_res.data.len() {
-                    //     return;
-                    // }
-                    self.texture.image_res.data[idx..idx + 4].clone_from_slice(&[
-                        self.color.0,
-                        self.color.1,
-                        self.color.2,
-                        (v * self.color.3 as f32) as u8,
-                    ]);
-                })
+    pub fn update_size(&mut self, width: i32, height: i32) {
+        self.screen_size = (width as f32, height as f32);
+    }
+
+    /// Takes the cells of the screen row by row, `cols` in each row
+    pub fn update(&mut self, cols: usize, cells: &[GlyphCell]) {
+        if cols != self.cols || cells.len() != self.cells.len() {
+            self.rebuild(cols, cells);
+            return;
+        }
+
+        let changed: Vec<usize> = (0..cells.len()).filter(|&i| cells[i] != self.cells[i]).collect();
+        for &i in changed.iter() {
+            self.cells[i] = cells[i];
+            self.build_cell(i);
+        }
+        // The atlas ran out of space and was cleared, unchanged cells point at stale glyphs
+        if self.atlas.generation() != self.atlas_generation {
+            self.rebuild(cols, cells);
+            return;
+        }
+
+        self.vbo.bind();
+        let mut runs = changed.iter().peekable();
+        while let Some(&start) = runs.next() {
+            let mut end = start + 1;
+            while runs.next_if(|&&i| i == end).is_some() {
+                end += 1;
             }
+            let range = start * 6..end * 6;
+            self.vbo.sub_data(range.start, &self.vertices[range]);
+            let range = self.glyph_start(start)..self.glyph_start(end);
+            self.vbo.sub_data(range.start, &self.vertices[range]);
         }
+        self.vbo.unbind();
     }
 
-    fn render_lines(&mut self, lines: &[String]) {
-        self.texture.image_res.data = vec![0; self.texture.image_res.data.len()];
+    fn rebuild(&mut self, cols: usize, cells: &[GlyphCell]) {
+        self.cols = cols;
+        self.cells = cells.to_vec();
+        self.vertices = vec![Vertex::empty(); cells.len() * (6 + GLYPH_VERTICES)];
+        // A clear of the full atlas partway leaves the cells before it pointing at wiped
+        // glyphs, so the screen is built again. A screen that never fits is drawn with
+        // what the last attempt left
+        for _ in 0..REBUILD_ATTEMPTS {
+            self.atlas_generation = self.atlas.generation();
+            for i in 0..cells.len() {
+                self.build_cell(i);
+            }
+            if self.atlas.generation() == self.atlas_generation {
+                break;
+            }
+        }
+        self.atlas_generation = self.atlas.generation();
 
-        for (line_no, line) in lines.iter().enumerate() {
-            self.render_text(line, line_no)
+        self.vbo.bind();
+        self.vbo.dynamic_draw_data(&self.vertices);
+        self.vbo.unbind();
+    }
+
+    fn build_cell(&mut self, i: usize) {
+        let cell = self.cells[i];
+        let x = (i % self.cols) as f32 * self.glyph_width as f32;
+        let y = (i / self.cols) as f32 * self.glyph_height as f32;
+
+        let background = &mut self.vertices[i * 6..(i + 1) * 6];
+        match cell.bg {
+            Some(bg) => {
+                let (u, v) = self.atlas.solid();
+                let (x1, y1) = (x + self.glyph_width as f32, y + self.glyph_height as f32);
+                background.copy_from_slice(&quad((x, y), (x1, y1), (u, v), (u, v), bg));
+            }
+            None => background.fill(Vertex::empty()),
+        }
+
+        let start = self.glyph_start(i);
+        self.build_glyph(start, cell.c, x, y, cell);
+
+        for (n, mark) in cell.marks.iter().enumerate() {
+            let start = start + 6 * (n + 1);
+            let glyph = mark.map(|c| self.font.glyph(c)).filter(|glyph| glyph.id().0 != 0);
+            match glyph {
+                Some(glyph) => {
+                    // Marks without an advance are designed to be drawn after the base
+                    // character and reach back over it
+                    let advance = glyph.scaled(self.scale).h_metrics();
+                    let shift = if advance.advance_width == 0.0 { self.glyph_width } else { 0 };
+                    self.build_glyph(start, mark.unwrap(), x + shift as f32, y, cell);
+                }
+                None => self.vertices[start..start + 6].fill(Vertex::empty()),
+            }
         }
     }
 
-    pub fn update_size(&mut self, width: i32, height: i32) {
-        self.texture = Texture2D::new_rgba(&self.gl, width as u32, height as u32);
+    /// Writes the quad of `c` drawn at `x`, `y` to the six vertices from `start`
+    fn build_glyph(&mut self, start: usize, c: char, x: f32, y: f32, cell: GlyphCell) {
+        let vertices = &mut self.vertices[start..start + 6];
+        let key = GlyphKey {
+            id: self.font.glyph(c).id(),
+            size: self.scale.y.to_bits(),
+            style: if cell.bold { Style::Bold } else { Style::Regular },
+        };
+        let glyph = match self.atlas.get(&self.font, key, self.ascent) {
+            Some(glyph) => glyph,
+            None => {
+                vertices.fill(Vertex::empty());
+                return;
+            }
+        };
+
+        let (x, y) = (x + glyph.left, y + glyph.top);
+        let (x1, y1) = (x + glyph.width, y + glyph.height);
+        vertices.copy_from_slice(&quad((x, y), (x1, y1), glyph.uv_min, glyph.uv_max, cell.fg));
     }
 
-    pub fn update(&mut self, lines: &[String]) {
-        self.render_lines(lines);
-        self.texture.bind();
-        self.texture.generate();
-        self.texture.unbind();
+    /// Index of the first glyph vertex of cell `i`, after the backgrounds of all cells
+    fn glyph_start(&self, i: usize) -> usize {
+        self.cells.len() * 6 + i * GLYPH_VERTICES
     }
 
     pub fn render(&self) {
         self.program.set_used();
+        self.program.set_vec2("screen_size", self.screen_size.0, self.screen_size.1);
+        self.program.set_vec2("offset", self.padding, self.padding);
 
-        self.texture.bind();
+        self.atlas.texture().bind();
         self.vao.bind();
 
         unsafe {
             self.gl.DrawArrays(
-                gl::TRIANGLES, // mode
-                0,             // starting index in the enabled arrays
-                6,             // number of indices to be rendered
+                gl::TRIANGLES,              // mode
+                0,                          // starting index in the enabled arrays
+                self.vertices.len() as i32, // number of indices to be rendered
             );
         }
     }
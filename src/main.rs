@@ -1,48 +1,133 @@
+pub mod config;
 pub mod graphics;
 pub mod processor;
+pub mod pty;
 pub mod render_gl;
 pub mod resources;
+pub mod session;
 pub mod shell;
+pub mod terminal;
 pub mod ui;
 
-use glutin::config::{Config, ConfigTemplateBuilder, GlConfig};
+use glutin::config::{ConfigTemplateBuilder, GlConfig};
+use glutin::context::PossiblyCurrentContext;
 use glutin::context::{ContextAttributesBuilder, GlProfile, NotCurrentGlContext, Version};
-use glutin::context::{NotCurrentContext, PossiblyCurrentContext};
 use glutin::display::{GetGlDisplay, GlDisplay};
+use glutin::surface::Surface;
 use glutin::surface::{GlSurface, WindowSurface};
-use glutin::surface::{Surface, SwapInterval};
 use glutin_winit::{DisplayBuilder, GlWindow};
 use winit::dpi::LogicalSize;
-use winit::event::{ElementState, Event, KeyEvent, WindowEvent};
-use winit::event_loop::{ControlFlow, EventLoop, EventLoopBuilder};
+use winit::event::{ElementState, Event, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::event_loop::{EventLoop, EventLoopBuilder};
 
 use nalgebra::Vector3;
 use raw_window_handle::HasRawWindowHandle;
 use render_gl::{ColorBuffer, Viewport};
+use pty::Pty;
+use config::Config;
 use resources::Resources;
-use shell::Shell;
-use ui::Ui;
+use session::{PtySession, Session};
+use shell::{History, Modifiers, Shell};
+use terminal::mouse;
+use ui::{palette::Palette, Ui};
+use winit::keyboard;
 use winit::window::{Window, WindowBuilder};
-use winit::{keyboard, window};
 
 use std::env;
-use std::ffi::{CStr, CString};
+use std::ffi::CString;
 use std::num::NonZeroU32;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Ok, Result};
 
-fn setup_gl() -> Result<(
-    gl::Gl,
-    EventLoop<()>,
-    (PossiblyCurrentContext, Surface<WindowSurface>, Window),
-)> {
+type GlState = (PossiblyCurrentContext, Surface<WindowSurface>, Window);
+
+/// What to run in the window: `--builtin` for the built-in shell,
+/// `-e <program>` for a program on a pty, `behavior.shell` or `$SHELL` otherwise.
+enum Mode {
+    Builtin,
+    Pty(String),
+}
+
+#[derive(Default)]
+struct Args {
+    builtin: bool,
+    /// `-e <program>`
+    program: Option<String>,
+    /// `--config <path>`, the default path otherwise
+    config: Option<PathBuf>,
+    /// `-o <section.key=value>` and the shorthands for it
+    options: Vec<String>,
+}
+
+impl Args {
+    fn parse() -> Args {
+        let mut args = env::args().skip(1);
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--builtin" => parsed.builtin = true,
+                "-e" | "--config" | "-o" | "--option" | "--font" | "--font-size"
+                | "--scrollback" => {
+                    let Some(value) = args.next() else {
+                        println!("{} expects a value", arg);
+                        continue;
+                    };
+                    match arg.as_str() {
+                        "-e" => parsed.program = Some(value),
+                        "--config" => parsed.config = Some(value.into()),
+                        // Shorthands for config options
+                        "--font" => parsed.options.push(format!("font.path={}", value)),
+                        "--font-size" => parsed.options.push(format!("font.size={}", value)),
+                        "--scrollback" => {
+                            parsed.options.push(format!("behavior.scrollback={}", value))
+                        }
+                        _ => parsed.options.push(value),
+                    }
+                }
+                _ => println!("Unknown argument: {}", arg),
+            }
+        }
+        parsed
+    }
+
+    fn mode(&self, config: &Config) -> Mode {
+        if self.builtin {
+            return Mode::Builtin;
+        }
+        let program = self.program.clone().or_else(|| config.behavior.shell.clone());
+        Mode::Pty(program.unwrap_or_else(Pty::default_program))
+    }
+}
+
+/// Modifier keys as the session sees them
+fn event_modifiers(state: keyboard::ModifiersState) -> Modifiers {
+    Modifiers { shift: state.shift_key(), ctrl: state.control_key(), alt: state.alt_key() }
+}
+
+/// Number of F1 to F12
+fn function_key(key: keyboard::NamedKey) -> Option<u8> {
+    use keyboard::NamedKey::*;
+    let keys = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+    keys.iter().position(|&k| k == key).map(|i| i as u8 + 1)
+}
+
+fn background(config: &Config) -> ColorBuffer {
+    let color = Palette::new(&config.colors).background;
+    ColorBuffer::from_color(Vector3::new(
+        color.0 as f32 / 255.0,
+        color.1 as f32 / 255.0,
+        color.2 as f32 / 255.0,
+    ))
+}
+
+fn setup_gl(size: &config::Window) -> Result<(gl::Gl, EventLoop<()>, GlState)> {
     let el = EventLoopBuilder::new().build()?;
 
     let window_builder = WindowBuilder::new()
         .with_title("Terminal")
         .with_transparent(false)
-        .with_inner_size(LogicalSize::new(1024_i32, 768_i32));
+        .with_inner_size(LogicalSize::new(size.width, size.height));
 
     let template = ConfigTemplateBuilder::default();
 
@@ -109,18 +194,44 @@ fn setup_gl() -> Result<(
 fn run() -> Result<()> {
     env::set_var("PYTHONPATH", "venv:scripts");
 
-    let (gl, el, state) = setup_gl()?;
+    let args = Args::parse();
+    let config = Config::load(args.config.as_deref(), &args.options)?;
+    let mut watcher = config::Watcher::new(args.config.clone(), args.options.clone());
+
+    let (gl, el, state) = setup_gl(&config.window)?;
 
-    let mut viewport = Viewport::for_window(1024, 768);
+    let mut viewport =
+        Viewport::for_window(config.window.width as i32, config.window.height as i32);
     viewport.set_used(&gl);
-    let color_buffer = ColorBuffer::from_color(Vector3::new(0.0, 0.0, 0.0));
+    let mut color_buffer = background(&config);
     color_buffer.set_used(&gl);
 
     let res = Resources::from_relative_exe_path(Path::new("assets"))?;
 
-    let ui = Ui::new(&res, &gl, viewport.w as u32, viewport.h as u32)?;
+    let ui = Ui::new(&res, &gl, viewport.w as u32, viewport.h as u32, &config)?;
 
-    let mut shell = Shell::new(ui)?;
+    let scrollback = config.behavior.scrollback;
+    let mut session = match args.mode(&config) {
+        Mode::Builtin => {
+            let history = History::default_path()
+                .map(History::load)
+                .transpose()
+                .unwrap_or_else(|e| {
+                    println!("History not loaded: {}", e);
+                    None
+                })
+                .unwrap_or_default();
+            Session::Builtin(Box::new(Shell::new(ui, scrollback, history)?))
+        }
+        Mode::Pty(program) => Session::Pty(Box::new(PtySession::new(ui, &program, scrollback)?)),
+    };
+    let mut modifiers = keyboard::ModifiersState::empty();
+    // Last position of the mouse in pixels and the button held
+    let mut mouse = (0.0, 0.0);
+    let mut mouse_button = None;
+    let mut clipboard = arboard::Clipboard::new()
+        .map_err(|e| println!("Clipboard is not available: {}", e))
+        .ok();
     let (gl_context, gl_surface, window) = &state;
 
     el.run(move |event, elwt| {
@@ -128,13 +239,24 @@ fn run() -> Result<()> {
 
         match event {
             Event::AboutToWait => {
+                if let Some(reloaded) = watcher.poll() {
+                    let reloaded = reloaded.map_err(|e| format!("{:#}", anyhow::Error::new(e)));
+                    if let std::result::Result::Ok(config) = &reloaded {
+                        color_buffer = background(config);
+                        color_buffer.set_used(&gl);
+                    }
+                    session.reload_config(reloaded.as_ref().map_err(String::as_str));
+                }
                 window.request_redraw();
             }
             Event::WindowEvent { event, .. } => {
                 match event {
                     WindowEvent::RedrawRequested => {
                         color_buffer.clear(&gl);
-                        shell.update();
+                        session.update();
+                        if !session.is_running() {
+                            elwt.exit();
+                        }
 
                         gl_surface.swap_buffers(gl_context).unwrap();
                     }
@@ -149,13 +271,62 @@ fn run() -> Result<()> {
                             NonZeroU32::new(physical_size.height).unwrap(),
                         );
 
-                        shell.handle_event(shell::Event::Resized(
+                        session.handle_event(shell::Event::Resized(
                             physical_size.width,
                             physical_size.height,
                         ));
                         window.request_redraw();
                     }
                     WindowEvent::CloseRequested => elwt.exit(),
+                    WindowEvent::ModifiersChanged(new_modifiers) => {
+                        modifiers = new_modifiers.state();
+                    }
+                    WindowEvent::MouseWheel { delta, .. } => {
+                        let lines = match delta {
+                            MouseScrollDelta::LineDelta(_, y) => y * 3.0,
+                            // Touchpads report pixels, roughly a line of text each 20
+                            MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                        };
+                        if lines as isize != 0 {
+                            session.handle_event(shell::Event::MouseWheel {
+                                lines: lines as isize,
+                                x: mouse.0,
+                                y: mouse.1,
+                                modifiers: event_modifiers(modifiers),
+                            });
+                            window.request_redraw();
+                        }
+                    }
+                    WindowEvent::CursorMoved { position, .. } => {
+                        mouse = (position.x as f32, position.y as f32);
+                        session.handle_event(shell::Event::MouseMove {
+                            button: mouse_button,
+                            x: mouse.0,
+                            y: mouse.1,
+                            modifiers: event_modifiers(modifiers),
+                        });
+                    }
+                    WindowEvent::MouseInput { state, button, .. } => {
+                        let button = match button {
+                            MouseButton::Left => mouse::Button::Left,
+                            MouseButton::Middle => mouse::Button::Middle,
+                            MouseButton::Right => mouse::Button::Right,
+                            _ => return,
+                        };
+                        let (x, y, modifiers) = (mouse.0, mouse.1, event_modifiers(modifiers));
+                        let event = match state {
+                            ElementState::Pressed => {
+                                mouse_button = Some(button);
+                                shell::Event::MouseDown { button, x, y, modifiers }
+                            }
+                            ElementState::Released => {
+                                mouse_button = None;
+                                shell::Event::MouseUp { button, x, y, modifiers }
+                            }
+                        };
+                        session.handle_event(event);
+                        window.request_redraw();
+                    }
                     WindowEvent::KeyboardInput {
                         device_id: _,
                         event:
@@ -168,12 +339,34 @@ fn run() -> Result<()> {
                     } => {
                         println!("{:#?}", c);
 
-                        let c = c.chars().nth(0);
-                        match c {
-                            Some('\u{8}' | '\r') => (), //backspace
+                        // Ctrl+Shift+C and Ctrl+Shift+V copy and paste, without Shift they
+                        // are control characters for the program
+                        let clipboard_key = modifiers.control_key() && modifiers.shift_key();
+                        if clipboard_key && c.eq_ignore_ascii_case("c") {
+                            let text = session.selection_text();
+                            if let (Some(clipboard), Some(text)) = (clipboard.as_mut(), text) {
+                                let _ = clipboard.set_text(text);
+                            }
+                            return;
+                        }
+                        if clipboard_key && c.eq_ignore_ascii_case("v") {
+                            let text = clipboard.as_mut().and_then(|c| c.get_text().ok());
+                            if let Some(text) = text {
+                                session.paste(&text);
+                                window.request_redraw();
+                            }
+                            return;
+                        }
+
+                        match c.chars().next() {
                             None => (),
-                            _ => {
-                                shell.handle_event(shell::Event::ReceivedCharacter(c.unwrap()));
+                            Some(c) if modifiers.control_key() || modifiers.alt_key() => {
+                                let modifiers = event_modifiers(modifiers);
+                                session.handle_event(shell::Event::Shortcut(c, modifiers));
+                                window.request_redraw();
+                            }
+                            Some(c) => {
+                                session.handle_event(shell::Event::ReceivedCharacter(c));
                                 // window.request_redraw();
                             }
                         }
@@ -190,35 +383,87 @@ fn run() -> Result<()> {
                     } => {
                         match keycode {
                             keyboard::NamedKey::Backspace => {
-                                shell.handle_event(shell::Event::Backspace);
+                                session.handle_event(shell::Event::Backspace);
                                 window.request_redraw();
                             }
 
                             keyboard::NamedKey::Space => {
-                                shell.handle_event(shell::Event::ReceivedCharacter(' '));
+                                session.handle_event(shell::Event::ReceivedCharacter(' '));
+                                window.request_redraw();
+                            }
+                            keyboard::NamedKey::Tab => {
+                                session.handle_event(shell::Event::ReceivedCharacter('\t'));
+                                window.request_redraw();
+                            }
+                            keyboard::NamedKey::Escape => {
+                                session.handle_event(shell::Event::ReceivedCharacter('\u{1b}'));
                                 window.request_redraw();
                             }
                             keyboard::NamedKey::Enter => {
-                                shell.handle_event(shell::Event::Enter);
+                                session.handle_event(shell::Event::Enter);
                                 window.request_redraw();
                             }
                             keyboard::NamedKey::ArrowLeft => {
-                                shell.handle_event(shell::Event::Left);
+                                session.handle_event(shell::Event::Left);
                                 window.request_redraw();
                             }
                             keyboard::NamedKey::ArrowRight => {
-                                shell.handle_event(shell::Event::Right);
+                                session.handle_event(shell::Event::Right);
                                 window.request_redraw();
                             }
                             keyboard::NamedKey::ArrowUp => {
-                                shell.handle_event(shell::Event::Previous);
+                                session.handle_event(shell::Event::Previous);
                                 window.request_redraw();
                             }
                             keyboard::NamedKey::ArrowDown => {
-                                shell.handle_event(shell::Event::Next);
+                                session.handle_event(shell::Event::Next);
                                 window.request_redraw();
                             }
-                            _ => (),
+                            keyboard::NamedKey::Home => {
+                                let modifiers = event_modifiers(modifiers);
+                                session.handle_event(shell::Event::Home(modifiers));
+                                window.request_redraw();
+                            }
+                            keyboard::NamedKey::End => {
+                                let modifiers = event_modifiers(modifiers);
+                                session.handle_event(shell::Event::End(modifiers));
+                                window.request_redraw();
+                            }
+                            keyboard::NamedKey::Delete => {
+                                let modifiers = event_modifiers(modifiers);
+                                session.handle_event(shell::Event::Delete(modifiers));
+                                window.request_redraw();
+                            }
+                            keyboard::NamedKey::PageUp if modifiers.shift_key() => {
+                                session.handle_event(shell::Event::ScrollPages(1));
+                                window.request_redraw();
+                            }
+                            keyboard::NamedKey::PageDown if modifiers.shift_key() => {
+                                session.handle_event(shell::Event::ScrollPages(-1));
+                                window.request_redraw();
+                            }
+                            keyboard::NamedKey::PageUp => {
+                                let modifiers = event_modifiers(modifiers);
+                                session.handle_event(shell::Event::PageUp(modifiers));
+                                window.request_redraw();
+                            }
+                            keyboard::NamedKey::PageDown => {
+                                let modifiers = event_modifiers(modifiers);
+                                session.handle_event(shell::Event::PageDown(modifiers));
+                                window.request_redraw();
+                            }
+                            keyboard::NamedKey::Insert => {
+                                let modifiers = event_modifiers(modifiers);
+                                session.handle_event(shell::Event::Insert(modifiers));
+                                window.request_redraw();
+                            }
+                            key => {
+                                if let Some(n) = function_key(key) {
+                                    let modifiers = event_modifiers(modifiers);
+                                    session.handle_event(shell::Event::Function(n, modifiers));
+                                    window.request_redraw();
+                                }
+                            }
                         };
                     }
                     WindowEvent::KeyboardInput {
@@ -231,7 +476,7 @@ fn run() -> Result<()> {
                             },
                         is_synthetic: _,
                     } => {
-                        shell.handle_event(shell::Event::Release);
+                        session.handle_event(shell::Event::Release);
                         window.request_redraw();
                     }
                     _ => (),
@@ -245,6 +490,6 @@ fn run() -> Result<()> {
 
 fn main() {
     if let Err(e) = run() {
-        println!("{}", e);
+        println!("{:#}", e);
     }
 }
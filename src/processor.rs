@@ -1,5 +1,15 @@
-use pyo3::{prelude::*, types::IntoPyDict};
-use std::{env, str};
+use pyo3::prelude::*;
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+    str,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// Held while Python runs in the directory of the shell, the directory of the process
+/// is shared by all its threads. `python-lib` is found relative to the directory, so it
+/// is held for imports too, always taken before the GIL
+static DIRECTORY: Mutex<()> = Mutex::new(());
 
 #[pyclass]
 #[derive(Default)]
@@ -17,8 +27,10 @@ impl LoggingStdout {
     }
 }
 
-pub fn process(cmd: &str) -> Vec<String> {
+/// Evaluates `cmd` in the `python-lib` namespace from the directory `cwd` of the shell
+pub fn process(cmd: &str, cwd: &Path) -> Vec<String> {
     env::set_var("PYTHONPATH", "./:venv");
+    let _lock = lock_directory();
     Python::with_gil(|py| -> PyResult<Vec<String>> {
         let sys = py.import("sys")?;
 
@@ -26,7 +38,15 @@ pub fn process(cmd: &str) -> Vec<String> {
         let locals = Some(python_lib.dict());
 
         sys.setattr("stdout", LoggingStdout::default().into_py(py))?;
-        let result = match py.eval(cmd, None, locals) {
+        // The process changes directory only while Python runs, `python-lib` is found
+        // relative to where it started
+        let directory = match InDirectory::enter(cwd) {
+            Ok(directory) => directory,
+            Err(e) => return Ok(vec![format!("{}: {}", cwd.display(), e), String::new()]),
+        };
+        let result = py.eval(cmd, None, locals);
+        drop(directory);
+        let result = match result {
             Ok(result) => result,
             Err(e) => return Ok(vec![e.to_string(), String::new()]),
         };
@@ -41,7 +61,94 @@ pub fn process(cmd: &str) -> Vec<String> {
         dbg!(stdout.clone());
         Ok(stdout)
     })
-    .unwrap()
+    .unwrap_or_else(|e| vec![e.to_string(), String::new()])
+}
+
+/// Directory of the process, never one Python is running in for the shell
+pub fn current_dir() -> io::Result<PathBuf> {
+    let _lock = lock_directory();
+    env::current_dir()
+}
+
+fn lock_directory() -> MutexGuard<'static, ()> {
+    DIRECTORY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The process moved to another directory while [`DIRECTORY`] is held, it goes back on
+/// drop
+struct InDirectory {
+    started_in: PathBuf,
+}
+
+impl InDirectory {
+    fn enter(dir: &Path) -> io::Result<InDirectory> {
+        let started_in = env::current_dir()?;
+        env::set_current_dir(dir)?;
+        Ok(InDirectory { started_in })
+    }
+}
+
+impl Drop for InDirectory {
+    fn drop(&mut self) {
+        let _ = env::set_current_dir(&self.started_in);
+    }
+}
+
+/// Names of the `python-lib` namespace and the builtins starting with `word`, or the
+/// attributes when it is a dotted path like `os.pa`. Private names are left out unless
+/// `word` asks for them
+pub fn complete(word: &str) -> Vec<String> {
+    env::set_var("PYTHONPATH", "./:venv");
+    let _lock = lock_directory();
+    Python::with_gil(|py| -> PyResult<Vec<String>> {
+        let namespace = py.import("python-lib")?.dict();
+        let (names, prefix, object) = match word.rsplit_once('.') {
+            Some((object, prefix)) => {
+                // Only a chain of names is evaluated, never a call
+                let valid = object.split('.').all(|name| {
+                    name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+                        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+                });
+                if !valid {
+                    return Ok(vec![]);
+                }
+                let names: Vec<String> = py.eval(object, None, Some(namespace))?.dir().extract()?;
+                (names, prefix, format!("{}.", object))
+            }
+            None => {
+                let mut names: Vec<String> = namespace.keys().extract()?;
+                names.extend(py.import("builtins")?.dir().extract::<Vec<String>>()?);
+                (names, word, String::new())
+            }
+        };
+        let mut names: Vec<String> = names
+            .into_iter()
+            .filter(|name| name.starts_with(prefix))
+            .filter(|name| !name.starts_with('_') || prefix.starts_with('_'))
+            .map(|name| format!("{}{}", object, name))
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    })
+    .unwrap_or_default()
+}
+
+/// Whether `name` is a global of the `python-lib` namespace
+pub fn defines(name: &str) -> bool {
+    env::set_var("PYTHONPATH", "./:venv");
+    let _lock = lock_directory();
+    Python::with_gil(|py| -> PyResult<bool> {
+        let namespace = py.import("python-lib")?.dict();
+        namespace.contains(name)
+    })
+    .unwrap_or(false)
+}
+
+/// Whether `name` is a Python builtin like `print`
+pub fn is_builtin(name: &str) -> bool {
+    env::set_var("PYTHONPATH", "./:venv");
+    Python::with_gil(|py| py.import("builtins")?.hasattr(name)).unwrap_or(false)
 }
 
 // #[derive(Default)]
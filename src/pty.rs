@@ -0,0 +1,149 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::process::CommandExt,
+    },
+    process::{Child, Command, Stdio},
+    ptr,
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to open pseudo-terminal")]
+    Open(#[source] io::Error),
+    #[error("Failed to spawn {program}")]
+    Spawn {
+        program: String,
+        #[source]
+        inner: io::Error,
+    },
+}
+
+/// A child process running on the slave side of a pseudo-terminal.
+///
+/// Output of the child is read on a separate thread and collected with [`Pty::read`],
+/// input is written straight to the master side.
+pub struct Pty {
+    master: File,
+    child: Child,
+    output: Receiver<Vec<u8>>,
+}
+
+impl Pty {
+    pub fn spawn(program: &str, cols: usize, rows: usize) -> Result<Pty, Error> {
+        let (master, slave) = open(cols, rows).map_err(Error::Open)?;
+
+        let mut command = Command::new(program);
+        command
+            .env("TERM", "xterm-256color")
+            .stdin(Stdio::from(slave.try_clone().map_err(Error::Open)?))
+            .stdout(Stdio::from(slave.try_clone().map_err(Error::Open)?))
+            .stderr(Stdio::from(slave));
+        unsafe {
+            command.pre_exec(|| {
+                // New session with the slave (already on stdin) as its controlling terminal,
+                // so the child gets job control and signals like Ctrl+C
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child =
+            command.spawn().map_err(|e| Error::Spawn { program: program.to_string(), inner: e })?;
+
+        let master = File::from(master);
+        let reader = master.try_clone().map_err(Error::Open)?;
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || read_loop(reader, sender));
+
+        Ok(Pty { master, child, output })
+    }
+
+    /// Program to run when none is given explicitly
+    pub fn default_program() -> String {
+        env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
+    }
+
+    /// Collects everything the child has written since the last call.
+    /// Returns `None` once the child closed its side of the terminal.
+    pub fn read(&self) -> Option<Vec<u8>> {
+        let mut data = vec![];
+        loop {
+            match self.output.try_recv() {
+                Ok(chunk) => data.extend(chunk),
+                Err(TryRecvError::Empty) => return Some(data),
+                Err(TryRecvError::Disconnected) if data.is_empty() => return None,
+                Err(TryRecvError::Disconnected) => return Some(data),
+            }
+        }
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.master.write_all(data)
+    }
+
+    pub fn resize(&self, cols: usize, rows: usize) {
+        let size = window_size(cols, rows);
+        unsafe {
+            libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ, &size);
+        }
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn open(cols: usize, rows: usize) -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut master = 0;
+    let mut slave = 0;
+    let size = window_size(cols, rows);
+    unsafe {
+        if libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null(), &size) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let (master, slave) = (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave));
+        // Neither end should leak into the child beyond its stdio
+        for fd in [&master, &slave] {
+            if libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok((master, slave))
+    }
+}
+
+fn window_size(cols: usize, rows: usize) -> libc::winsize {
+    libc::winsize { ws_row: rows as u16, ws_col: cols as u16, ws_xpixel: 0, ws_ypixel: 0 }
+}
+
+fn read_loop(mut reader: File, sender: Sender<Vec<u8>>) {
+    let mut buf = [0; 4096];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                if sender.send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            // EIO is returned once the child is gone and the slave is closed
+            Err(_) => break,
+        }
+    }
+}
@@ -35,12 +35,36 @@ impl<const BUFFER_TYPE: gl::types::GLuint> Buffer<BUFFER_TYPE> {
         unsafe {
             self.gl.BufferData(
                 BUFFER_TYPE,                                                        // target
-                (data.len() * ::std::mem::size_of::<T>()) as gl::types::GLsizeiptr, // size of data in bytes
+                ::std::mem::size_of_val(data) as gl::types::GLsizeiptr, // size of data in bytes
                 data.as_ptr() as *const gl::types::GLvoid, // pointer to data
                 gl::STATIC_DRAW,                           // usage
             );
         }
     }
+
+    /// Allocates the buffer for data that is rewritten often with `sub_data`
+    pub fn dynamic_draw_data<T>(&self, data: &[T]) {
+        unsafe {
+            self.gl.BufferData(
+                BUFFER_TYPE,
+                ::std::mem::size_of_val(data) as gl::types::GLsizeiptr,
+                data.as_ptr() as *const gl::types::GLvoid,
+                gl::DYNAMIC_DRAW,
+            );
+        }
+    }
+
+    /// Overwrites part of the buffer, `offset` counts elements of `T`
+    pub fn sub_data<T>(&self, offset: usize, data: &[T]) {
+        unsafe {
+            self.gl.BufferSubData(
+                BUFFER_TYPE,
+                (offset * ::std::mem::size_of::<T>()) as gl::types::GLintptr,
+                ::std::mem::size_of_val(data) as gl::types::GLsizeiptr,
+                data.as_ptr() as *const gl::types::GLvoid,
+            );
+        }
+    }
 }
 
 impl<const BUFFER_TYPE: gl::types::GLuint> Drop for Buffer<BUFFER_TYPE> {
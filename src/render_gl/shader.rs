@@ -114,6 +114,13 @@ impl Program {
         }
     }
 
+    pub fn set_vec2(&self, name: &str, x: f32, y: f32) {
+        let cname = std::ffi::CString::new(name).expect("CString::new failed");
+        unsafe {
+            self.gl.Uniform2f(self.gl.GetUniformLocation(self.id, cname.as_ptr()), x, y);
+        }
+    }
+
     pub fn set_matrix(&self, name: &str, mat: Matrix4<f32>) {
         let cname = std::ffi::CString::new(name).expect("CString::new failed");
         unsafe {
@@ -228,6 +235,6 @@ fn shader_from_source(gl: &gl::Gl, source: &CStr, shader_type: GLuint) -> Result
 
 fn create_whitespace_cstring_with_len(len: usize) -> CString {
     // allocate buffer of correct size
-    let buffer: Vec<u8> = vec![b' '; len as usize + 1];
+    let buffer: Vec<u8> = vec![b' '; len + 1];
     unsafe { CString::from_vec_unchecked(buffer) }
 }
@@ -53,6 +53,13 @@ impl Texture2D {
         Texture2D::new(gl, img_res, gl::RGBA)
     }
 
+    /// Single channel texture, used for glyph coverage
+    pub fn new_red(gl: &gl::Gl, width: u32, height: u32) -> Texture2D {
+        let data = vec![0; (width * height) as usize];
+        let img_res = ImageResource { data, width, height };
+        Texture2D::new(gl, img_res, gl::RED)
+    }
+
     pub fn generate(&self) {
         unsafe {
             self.gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
@@ -90,6 +97,25 @@ impl Texture2D {
         }
     }
 
+    /// Uploads `data` into a part of the texture, rows are tightly packed
+    pub fn update_region(&self, x: u32, y: u32, width: u32, height: u32, data: &[u8]) {
+        unsafe {
+            self.gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            self.gl.TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                x as i32,
+                y as i32,
+                width as i32,
+                height as i32,
+                self.format,
+                gl::UNSIGNED_BYTE,
+                data.as_ptr() as *const gl::types::GLvoid,
+            );
+            self.gl.PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        }
+    }
+
     pub fn activate(&self, unit: gl::types::GLenum) {
         unsafe {
             self.gl.ActiveTexture(unit);
@@ -29,6 +29,8 @@ pub enum Error {
     FailedToGetExePath,
     #[error("Invald path")]
     InvalidPath,
+    #[error("File is not a TrueType font")]
+    InvalidFont,
 }
 
 pub struct ImageResource {
@@ -37,6 +39,7 @@ pub struct ImageResource {
     pub height: u32,
 }
 
+#[derive(Clone)]
 pub struct Resources {
     root_path: PathBuf,
 }
@@ -58,7 +61,7 @@ impl Resources {
         file.read_to_end(&mut buffer)?;
 
         // check for null byte
-        if buffer.iter().any(|i| *i == 0) {
+        if buffer.contains(&0) {
             return Err(Error::FileContainsNil);
         }
 
@@ -81,12 +84,15 @@ impl Resources {
 
         let mut font_data: Vec<u8> = Vec::with_capacity(file.metadata()?.len() as usize + 1);
         file.read_to_end(&mut font_data)?;
-        let font = Font::try_from_vec(font_data).expect("error constructing a Font from bytes");
-        Ok(font)
+        Font::try_from_vec(font_data).ok_or(Error::InvalidFont)
     }
 }
 
 fn resource_name_to_path(root_dir: &Path, location: &str) -> PathBuf {
+    if Path::new(location).is_absolute() {
+        return location.into();
+    }
+
     let mut path: PathBuf = root_dir.into();
 
     for part in location.split('/') {
@@ -0,0 +1,269 @@
+use crate::{
+    config::Config,
+    pty::Pty,
+    shell::{Event, Modifiers, Shell},
+    terminal::{
+        mouse::{MouseEvent, Tracking},
+        Terminal,
+    },
+    ui::{Ui, View},
+};
+
+/// Maps Ctrl+<key> to the control character a terminal sends for it, e.g. Ctrl+C to ETX
+fn control_character(c: char) -> Option<char> {
+    match c.to_ascii_uppercase() {
+        c @ '@'..='_' => Some((c as u8 & 0x1f) as char),
+        '?' => Some('\u{7f}'),
+        _ => None,
+    }
+}
+
+/// What is running inside the window
+pub enum Session {
+    Builtin(Box<Shell>),
+    Pty(Box<PtySession>),
+}
+
+impl Session {
+    pub fn handle_event(&mut self, event: Event) {
+        match self {
+            Session::Builtin(shell) => shell.handle_event(event),
+            Session::Pty(pty) => pty.handle_event(event),
+        }
+    }
+
+    pub fn update(&mut self) {
+        match self {
+            Session::Builtin(shell) => shell.update(),
+            Session::Pty(pty) => pty.update(),
+        }
+    }
+
+    /// Applies a changed config file, `Err` holds why it could not be loaded
+    pub fn reload_config(&mut self, config: Result<&Config, &str>) {
+        match self {
+            Session::Builtin(shell) => shell.reload_config(config),
+            Session::Pty(pty) => pty.reload_config(config),
+        }
+    }
+
+    /// Text selected with the mouse, to be copied to the clipboard
+    pub fn selection_text(&self) -> Option<String> {
+        match self {
+            Session::Builtin(shell) => shell.selection_text(),
+            Session::Pty(pty) => pty.selection_text(),
+        }
+    }
+
+    /// Sends text from the clipboard as if it was typed at once
+    pub fn paste(&mut self, text: &str) {
+        match self {
+            Session::Builtin(shell) => shell.paste(text),
+            Session::Pty(pty) => pty.paste(text),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        match self {
+            Session::Builtin(_) => true,
+            Session::Pty(pty) => pty.running,
+        }
+    }
+}
+
+/// A program connected through a pseudo-terminal
+pub struct PtySession {
+    ui: Ui,
+    pty: Pty,
+    terminal: Terminal,
+    running: bool,
+    /// Cell the mouse was last reported at, motion inside a cell is not reported
+    mouse_cell: Option<(usize, usize)>,
+}
+
+impl PtySession {
+    pub fn new(ui: Ui, program: &str, scrollback: usize) -> Result<Self, anyhow::Error> {
+        let (cols, rows) = ui.size_in_cells();
+        let pty = Pty::spawn(program, cols, rows)?;
+        let terminal = Terminal::with_scrollback(cols, rows, scrollback);
+        let mut session = PtySession { ui, pty, terminal, running: true, mouse_cell: None };
+        session.draw_buffer();
+        anyhow::Ok(session)
+    }
+
+    pub fn handle_event(&mut self, event: Event) {
+        self.ui.handle_event(event);
+        if event.is_input() {
+            self.terminal.reset_display_offset();
+            self.draw_buffer();
+        }
+        let input: &[u8] = match event {
+            Event::Resized(_, _) => {
+                self.resize();
+                return;
+            }
+            Event::ReceivedCharacter(c) => {
+                let mut buf = [0; 4];
+                let _ = self.pty.write(c.encode_utf8(&mut buf).as_bytes());
+                return;
+            }
+            Event::Shortcut(c, modifiers) => {
+                let Some(c) = (if modifiers.ctrl { control_character(c) } else { Some(c) }) else {
+                    return;
+                };
+                // Alt sends the key prefixed with ESC
+                let mut input = if modifiers.alt { vec![0x1b] } else { vec![] };
+                input.extend(c.to_string().as_bytes());
+                let _ = self.pty.write(&input);
+                return;
+            }
+            Event::Home(modifiers)
+            | Event::End(modifiers)
+            | Event::Delete(modifiers)
+            | Event::Insert(modifiers)
+            | Event::PageUp(modifiers)
+            | Event::PageDown(modifiers)
+            | Event::Function(_, modifiers) => {
+                let _ = self.pty.write(&self.key_sequence(event, modifiers));
+                return;
+            }
+            Event::Backspace => b"\x7f",
+            Event::Enter => b"\r",
+            // Application cursor keys mode changes the prefix of arrows
+            Event::Left if self.terminal.modes().cursor_keys => b"\x1bOD",
+            Event::Right if self.terminal.modes().cursor_keys => b"\x1bOC",
+            Event::Previous if self.terminal.modes().cursor_keys => b"\x1bOA",
+            Event::Next if self.terminal.modes().cursor_keys => b"\x1bOB",
+            Event::Left => b"\x1b[D",
+            Event::Right => b"\x1b[C",
+            Event::Previous => b"\x1b[A",
+            Event::Next => b"\x1b[B",
+            Event::ScrollPages(pages) => {
+                self.terminal.scroll_display(pages * self.terminal.grid().rows() as isize);
+                self.draw_buffer();
+                return;
+            }
+            Event::Release => return,
+            _ if self.report_mouse(event) => return,
+            Event::MouseWheel { lines, .. } => {
+                self.terminal.scroll_display(lines);
+                self.draw_buffer();
+                return;
+            }
+            Event::MouseDown { .. } | Event::MouseUp { .. } | Event::MouseMove { .. } => {
+                if self.ui.handle_mouse(event, self.terminal.grid()) {
+                    self.ui.update_text(self.terminal.grid());
+                }
+                return;
+            }
+        };
+        let _ = self.pty.write(input);
+    }
+
+    /// Escape sequence of Home, End, editing and function keys, modifiers are sent as a
+    /// parameter like xterm does, e.g. `ESC[1;5H` for Ctrl+Home or `ESC[5;5~` for
+    /// Ctrl+PageUp
+    fn key_sequence(&self, event: Event, modifiers: Modifiers) -> Vec<u8> {
+        let parameter = 1
+            + if modifiers.shift { 1 } else { 0 }
+            + if modifiers.alt { 2 } else { 0 }
+            + if modifiers.ctrl { 4 } else { 0 };
+        let (number, end) = match event {
+            Event::Home(_) => (1, 'H'),
+            Event::End(_) => (1, 'F'),
+            Event::Insert(_) => (2, '~'),
+            Event::PageUp(_) => (5, '~'),
+            Event::PageDown(_) => (6, '~'),
+            // F1 to F4 are SS3 P to S, the others numbered with gaps left by VT220 keys
+            Event::Function(n @ 1..=4, _) => (1, (b'O' + n) as char),
+            Event::Function(n, _) => ([15, 17, 18, 19, 20, 21, 23, 24][n as usize - 5], '~'),
+            _ => (3, '~'),
+        };
+        let sequence = match (parameter, end) {
+            // Application cursor keys mode changes the prefix of Home and End like arrows
+            (1, 'H' | 'F') if self.terminal.modes().cursor_keys => format!("\x1bO{}", end),
+            (1, 'H' | 'F') => format!("\x1b[{}", end),
+            (1, 'P'..='S') => format!("\x1bO{}", end),
+            (1, _) => format!("\x1b[{}{}", number, end),
+            _ => format!("\x1b[{};{}{}", number, parameter, end),
+        };
+        sequence.into_bytes()
+    }
+
+    /// Sends a mouse event to the program if it asked for mouse reports, returns whether
+    /// the program takes the mouse. With Shift held it is kept for selecting and scrolling
+    fn report_mouse(&mut self, event: Event) -> bool {
+        let (event, x, y, modifiers) = match event {
+            Event::MouseDown { button, x, y, modifiers } => {
+                (MouseEvent::Press(button), x, y, modifiers)
+            }
+            Event::MouseUp { button, x, y, modifiers } => {
+                (MouseEvent::Release(button), x, y, modifiers)
+            }
+            Event::MouseMove { button, x, y, modifiers } => {
+                (MouseEvent::Motion(button), x, y, modifiers)
+            }
+            Event::MouseWheel { lines, x, y, modifiers } if lines > 0 => {
+                (MouseEvent::WheelUp, x, y, modifiers)
+            }
+            Event::MouseWheel { x, y, modifiers, .. } => (MouseEvent::WheelDown, x, y, modifiers),
+            _ => return false,
+        };
+        if self.terminal.modes().mouse_tracking == Tracking::Off || modifiers.shift {
+            return false;
+        }
+
+        let (col, row) = self.ui.cell_at(x, y, self.terminal.grid());
+        if matches!(event, MouseEvent::Motion(_)) && self.mouse_cell == Some((col, row)) {
+            return true;
+        }
+        self.mouse_cell = Some((col, row));
+        if let Some(report) = self.terminal.mouse_report(event, col, row, modifiers) {
+            let _ = self.pty.write(&report);
+        }
+        true
+    }
+
+    pub fn selection_text(&self) -> Option<String> {
+        self.ui.selection_text(self.terminal.grid())
+    }
+
+    /// Writes pasted text to the program, see [`Terminal::paste`]
+    pub fn paste(&mut self, text: &str) {
+        let _ = self.pty.write(&self.terminal.paste(text));
+        self.terminal.reset_display_offset();
+        self.draw_buffer();
+    }
+
+    pub fn update(&mut self) {
+        match self.pty.read() {
+            Some(output) if !output.is_empty() => {
+                self.terminal.advance(&output);
+                let _ = self.pty.write(&self.terminal.take_responses());
+                self.draw_buffer();
+            }
+            Some(_) => (),
+            None => self.running = false,
+        }
+        self.ui.render();
+    }
+
+    pub fn reload_config(&mut self, config: Result<&Config, &str>) {
+        self.ui.reload_config(config);
+        self.resize();
+    }
+
+    /// Fits the terminal and the program behind the pty to the cells the window has room for
+    fn resize(&mut self) {
+        let (cols, rows) = self.ui.size_in_cells();
+        self.terminal.resize(cols, rows);
+        self.pty.resize(cols, rows);
+        self.draw_buffer();
+    }
+
+    fn draw_buffer(&mut self) {
+        self.ui.update_text(self.terminal.grid());
+        self.ui.update_cursor(self.terminal.grid());
+        self.ui.set_cursor_visible(self.terminal.modes().show_cursor);
+    }
+}
@@ -1,7 +1,49 @@
 use crate::{
-    processor::process,
-    ui::{textdisplay::Buffer, Ui},
+    config::Config,
+    processor::{self, process},
+    terminal::{
+        mouse::Button,
+        parser::{Action, Erase},
+        Terminal,
+    },
+    ui::{Ui, View},
 };
+use std::{collections::VecDeque, os::fd::OwnedFd};
+use unicode_segmentation::UnicodeSegmentation;
+
+use self::{
+    completion::Completion,
+    directories::Directories,
+    input::Input,
+    job::{Job, Program, Stage, Streams},
+    killring::KillRing,
+    parser::{Connector, Pipeline},
+    search::Search,
+};
+
+pub use self::history::History;
+
+mod builtins;
+mod completion;
+mod directories;
+mod files;
+mod history;
+mod input;
+mod job;
+mod killring;
+mod layout;
+mod parser;
+mod search;
+#[cfg(test)]
+mod testing;
+
+/// Modifier keys held during an event
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+}
 
 #[derive(Copy, Clone, Debug)]
 pub enum Event {
@@ -13,96 +55,518 @@ pub enum Event {
     Right,
     Previous,
     Next,
+    Home(Modifiers),
+    End(Modifiers),
+    Delete(Modifiers),
+    Insert(Modifiers),
+    PageUp(Modifiers),
+    PageDown(Modifiers),
+    /// F1 to F12
+    Function(u8, Modifiers),
+    /// Character key pressed with Ctrl or Alt held
+    Shortcut(char, Modifiers),
+    ScrollPages(isize),
     Release,
+    /// Mouse button pressed at a position in pixels of the window
+    MouseDown {
+        button: Button,
+        x: f32,
+        y: f32,
+        modifiers: Modifiers,
+    },
+    MouseUp {
+        button: Button,
+        x: f32,
+        y: f32,
+        modifiers: Modifiers,
+    },
+    /// Mouse moved, `button` is the one held if any
+    MouseMove {
+        button: Option<Button>,
+        x: f32,
+        y: f32,
+        modifiers: Modifiers,
+    },
+    /// Wheel turned by `lines` over a position in pixels, positive values scroll back
+    MouseWheel {
+        lines: isize,
+        x: f32,
+        y: f32,
+        modifiers: Modifiers,
+    },
+}
+
+impl Event {
+    /// Whether the event is the user typing, which brings the view back to the bottom
+    pub fn is_input(&self) -> bool {
+        !(self.is_mouse()
+            || matches!(self, Event::Resized(_, _) | Event::ScrollPages(_) | Event::Release))
+    }
+
+    pub fn is_mouse(&self) -> bool {
+        matches!(
+            self,
+            Event::MouseDown { .. }
+                | Event::MouseUp { .. }
+                | Event::MouseMove { .. }
+                | Event::MouseWheel { .. }
+        )
+    }
+}
+
+/// What the previous key did, kills in a row are joined and Alt+Y only follows a yank
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Command {
+    Kill,
+    /// Number of graphemes yanked
+    Yank(usize),
+    Other,
 }
 
-pub struct Shell {
-    ui: Ui,
-    history: Vec<String>,
-    input: String,
-    cmd_history: Vec<String>,
+pub struct Shell<V: View = Ui> {
+    ui: V,
+    terminal: Terminal,
+    input: Input,
+    /// Absolute row and column where the input starts, right after the prompt
+    input_origin: (usize, usize),
+    history: History,
+    /// Entry of the history shown by Up and Down, its length when none is
     cmd_pointer: usize,
-    cursor_pos: usize,
+    kill_ring: KillRing,
+    last_command: Command,
+    /// Reverse search of the history shown instead of the prompt while Ctrl+R is active
+    search: Option<Search>,
+    dirs: Directories,
+    /// Pipeline of programs running, the prompt comes back when it exits
+    job: Option<Job>,
+    /// Pipelines of the line left to run after the job
+    queue: VecDeque<(Connector, Pipeline)>,
+    /// Exit status of the last pipeline, `&&` and `||` depend on it
+    status: i32,
 }
 
-impl Shell {
-    pub fn new(ui: Ui) -> Result<Self, anyhow::Error> {
-        let username = "user".to_string();
-        let directory = "/".to_string();
+impl<V: View> Shell<V> {
+    pub fn new(ui: V, scrollback: usize, history: History) -> Result<Self, anyhow::Error> {
+        let (cols, rows) = ui.size_in_cells();
         let mut shell = Shell {
             ui,
-            history: vec![format!("{}:{}$ ", username, directory)],
-            input: String::new(),
-            cmd_history: vec![],
-            cmd_pointer: 0,
-            cursor_pos: 0,
+            terminal: Terminal::with_scrollback(cols, rows, scrollback),
+            input: Input::default(),
+            input_origin: (0, 0),
+            cmd_pointer: history.entries().len(),
+            history,
+            kill_ring: KillRing::default(),
+            last_command: Command::Other,
+            search: None,
+            dirs: Directories::new(processor::current_dir()?),
+            job: None,
+            queue: VecDeque::new(),
+            status: 0,
         };
-        shell.draw_buffer();
-        shell.move_cursor_to_end();
+        shell.print_prompt();
+        shell.draw_input();
         anyhow::Ok(shell)
     }
 
     pub fn handle_event(&mut self, event: Event) {
         self.ui.handle_event(event);
-        match event {
-            Event::Resized(_, _) => {
-                self.draw_buffer();
-            }
-            Event::ReceivedCharacter(c) => {
-                self.input.insert(self.cursor_pos, c);
-                self.shift_cursor(1);
-                self.draw_buffer();
-            }
-            Event::Backspace => {
-                if self.cursor_pos > 0 {
-                    self.shift_cursor(-1);
-                    self.input.remove(self.cursor_pos);
-                    self.draw_buffer();
+        let mut previous_command = self.last_command;
+        if event.is_input() {
+            self.terminal.reset_display_offset();
+            previous_command = std::mem::replace(&mut self.last_command, Command::Other);
+            if let Some(job) = &self.job {
+                // Ctrl+C stops the program, other keys are dropped until the prompt is back
+                if matches!(event, Event::Shortcut(c, m) if m.ctrl && c.eq_ignore_ascii_case(&'c'))
+                {
+                    job.interrupt();
+                    self.queue.clear();
+                    self.print("^C");
                 }
+                return;
             }
+            if self.search.is_some() && self.search_event(event) {
+                return;
+            }
+        }
+        match event {
+            Event::Resized(_, _) => self.resize(),
+            // Control characters only make sense to programs behind a pty
+            Event::ReceivedCharacter('\t') => self.complete(),
+            Event::ReceivedCharacter(c) if !c.is_control() => self.edit(|input| input.insert(c)),
+            Event::Backspace => self.edit(Input::backspace),
             Event::Enter => {
-                if let Some(last) = self.history.last_mut() {
-                    last.push_str(&self.input);
-                } else {
-                    self.history.push(self.input.clone());
+                self.move_cursor_to_end();
+                // The suggestion is not part of the line that runs
+                self.terminal.perform(Action::EraseInDisplay(Erase::ToEnd));
+                self.print("\n");
+                let line = self.input.text().to_string();
+                if let Err(e) = self.history.push(&line) {
+                    println!("History not saved: {}", e);
                 }
-                self.cmd_history.push(self.input.clone());
-                self.cmd_pointer += 1;
-                let output = self.process_cmd(&self.input);
-                self.history.extend(output);
-
+                self.cmd_pointer = self.history.entries().len();
                 self.input.clear();
-                self.draw_buffer();
-                self.move_cursor_to_end();
+                self.process_cmd(&line);
+                if self.job.is_none() {
+                    self.finish_command();
+                }
             }
             Event::Left => self.shift_cursor(-1),
-            Event::Right => self.shift_cursor(1),
+            Event::Right if !self.accept_suggestion(false) => self.shift_cursor(1),
             Event::Previous => self.previous_input(),
             Event::Next => self.next_input(),
+            Event::Home(_) => self.edit(Input::move_to_start),
+            Event::End(_) if !self.accept_suggestion(false) => self.edit(Input::move_to_end),
+            Event::Delete(_) => self.edit(Input::delete),
+            Event::Shortcut(c, modifiers) => self.shortcut(c, modifiers, previous_command),
+            Event::MouseWheel { lines, .. } => {
+                self.terminal.scroll_display(lines);
+                self.draw_input();
+            }
+            Event::ScrollPages(pages) => {
+                self.terminal.scroll_display(pages * self.terminal.grid().rows() as isize);
+                self.draw_input();
+            }
+            Event::MouseDown { .. } | Event::MouseUp { .. } | Event::MouseMove { .. }
+                if self.ui.handle_mouse(event, self.terminal.grid()) =>
+            {
+                self.ui.update_text(self.terminal.grid());
+            }
             _ => (),
         }
-        self.ui.update_cursor(&self.history);
+    }
+
+    /// Text selected with the mouse
+    pub fn selection_text(&self) -> Option<String> {
+        self.ui.selection_text(self.terminal.grid())
+    }
+
+    /// Inserts pasted text into the input as one edit, lines are joined by spaces
+    pub fn paste(&mut self, text: &str) {
+        let text: String = text
+            .trim_end_matches(['\r', '\n'])
+            .chars()
+            .filter_map(|c| match c {
+                '\n' | '\t' => Some(' '),
+                c if c.is_control() => None,
+                c => Some(c),
+            })
+            .collect();
+        self.terminal.reset_display_offset();
+        self.edit(|input| input.insert_str(&text));
     }
 
     pub fn update(&mut self) {
+        self.poll_job();
         self.ui.render();
     }
 
-    fn draw_buffer(&mut self) {
-        let buffer = Buffer::new(self.history.clone(), &self.input);
-        self.ui.update_text(&buffer);
+    /// Shows what the running programs wrote, then runs the rest of the line or shows
+    /// the prompt once they exited
+    fn poll_job(&mut self) {
+        let Some(job) = self.job.as_mut() else {
+            return;
+        };
+        match job.read() {
+            Some(data) if data.is_empty() => (),
+            Some(data) => {
+                // Programs write to a pipe, not a terminal that would translate newlines
+                let mut bytes = Vec::with_capacity(data.len());
+                for b in data {
+                    if b == b'\n' {
+                        bytes.push(b'\r');
+                    }
+                    bytes.push(b);
+                }
+                self.terminal.advance(&bytes);
+                self.ui.update_text(self.terminal.grid());
+                self.ui.update_cursor(self.terminal.grid());
+            }
+            None => {
+                self.status = job.status().unwrap_or(1);
+                self.job = None;
+                self.run_queue();
+                if self.job.is_none() {
+                    self.finish_command();
+                }
+            }
+        }
+    }
+
+    /// Prints the prompt for the next command on a line of its own
+    fn finish_command(&mut self) {
+        if self.terminal.grid().cursor().col > 0 {
+            self.print("\n");
+        }
+        self.print_prompt();
+        self.draw_input();
+    }
+
+    pub fn reload_config(&mut self, config: Result<&Config, &str>) {
+        self.ui.reload_config(config);
+        self.resize();
+    }
+
+    /// Fits the terminal to the cells the window has room for
+    fn resize(&mut self) {
+        let (cols, rows) = self.ui.size_in_cells();
+        self.terminal.resize(cols, rows);
+        self.draw_input();
+    }
+
+    /// Writes text to the screen, `\n` starts a new line
+    fn print(&mut self, text: &str) {
+        self.terminal.advance(text.replace('\n', "\r\n").as_bytes());
+    }
+
+    fn print_prompt(&mut self) {
+        let prompt =
+            format!("{}@{}:{}$ ", username(), hostname(), directories::display(self.dirs.cwd()));
+        self.print(&prompt);
+        let grid = self.terminal.grid();
+        let cursor = grid.cursor();
+        self.input_origin = (grid.absolute_row(cursor.row), cursor.col);
+    }
+
+    /// Redraws the input after the prompt and puts the cursor at the input cursor, or
+    /// the search in place of the prompt while there is one. Nothing is drawn over the
+    /// output of a running program
+    fn draw_input(&mut self) {
+        if self.job.is_some() {
+            self.ui.update_text(self.terminal.grid());
+            self.ui.update_cursor(self.terminal.grid());
+            return;
+        }
+        if let Some(search) = &self.search {
+            let (before, matched, after) = search.entry(self.history.entries()).unwrap_or_default();
+            let before = format!("{}{}", search.label(), before);
+            // The part matching the query is drawn inverted
+            let after = format!("\x1b[7m{}\x1b[27m{}", matched, after);
+            self.draw_line((self.input_origin.0, 0), &before, &after);
+        } else {
+            let suggestion = self.suggestion().map(|rest| format!("\x1b[2m{}\x1b[22m", rest));
+            let (before, after) = self.input.split();
+            let (before, after) =
+                (before.to_string(), after.to_string() + &suggestion.unwrap_or_default());
+            self.draw_line(self.input_origin, &before, &after);
+        }
+    }
+
+    /// Completes the word before the cursor up to where the candidates differ and lists
+    /// them under the input when there are several
+    fn complete(&mut self) {
+        let (before, _) = self.input.split();
+        let completion =
+            completion::complete(before, builtins::NAMES, self.dirs.cwd(), processor::complete);
+        let word = before[completion.start..].to_string();
+
+        let mut text = completion.common_prefix();
+        if let [candidate] = completion.candidates.as_slice() {
+            if !candidate.text.ends_with('/') {
+                text.push(' ');
+            }
+        }
+        // The typed word can be quoted differently from the candidates
+        if !completion.candidates.is_empty() && text != word {
+            self.edit(|input| {
+                input.erase_back(word.graphemes(true).count());
+                input.insert_str(&text);
+            });
+        }
+        if completion.candidates.len() > 1 {
+            self.list_candidates(&completion);
+        }
+    }
+
+    /// Prints the candidates in columns and the prompt with the input again below them
+    fn list_candidates(&mut self, completion: &Completion) {
+        let cursor = self.input.split().0.graphemes(true).count();
+        self.move_cursor_to_end();
+        self.terminal.perform(Action::EraseInDisplay(Erase::ToEnd));
+        let names: Vec<String> =
+            completion.candidates.iter().map(|candidate| candidate.display.clone()).collect();
+        let listing = layout::columns(&names, self.terminal.grid().cols());
+        self.print("\n");
+        self.print(&listing);
+        self.print_prompt();
+        self.input.move_to_start();
+        self.input.shift_cursor(cursor as isize);
+        self.draw_input();
+    }
+
+    /// Rest of the newest history entry starting with the input, shown dimmed after it
+    /// while the cursor is at its end
+    fn suggestion(&self) -> Option<&str> {
+        if !self.input.split().1.is_empty() {
+            return None;
+        }
+        self.history.suggest(self.input.text())
+    }
+
+    /// Adds the suggestion to the input, only up to the end of its first word if `word`.
+    /// Returns whether there was one
+    fn accept_suggestion(&mut self, word: bool) -> bool {
+        let Some(rest) = self.suggestion() else {
+            return false;
+        };
+        let mut accepted = Input::default();
+        accepted.set(rest);
+        if word {
+            accepted.move_to_start();
+            accepted.word_forward();
+        }
+        let accepted = accepted.split().0.to_string();
+        self.edit(|input| input.insert_str(&accepted));
+        true
+    }
+
+    /// Writes `before` and `after` from the absolute `origin` to the end of the screen and
+    /// leaves the cursor between them
+    fn draw_line(&mut self, origin: (usize, usize), before: &str, after: &str) {
+        let (origin_row, origin_col) = origin;
+        let row = self.terminal.grid().screen_row(origin_row).unwrap_or(0);
+        self.terminal.perform(Action::CursorPosition { row, col: origin_col });
+        self.terminal.perform(Action::EraseInDisplay(Erase::ToEnd));
+
+        self.print(before);
+        let grid = self.terminal.grid();
+        let cursor = grid.cursor();
+        let cursor_row = grid.absolute_row(cursor.row);
+        self.print(after);
+        let row = self.terminal.grid().screen_row(cursor_row).unwrap_or(0);
+        self.terminal.perform(Action::CursorPosition { row, col: cursor.col });
+
+        self.ui.update_text(self.terminal.grid());
+        self.ui.update_cursor(self.terminal.grid());
+    }
+
+    /// Readline bindings of Ctrl and Alt with a character key
+    fn shortcut(&mut self, c: char, modifiers: Modifiers, previous_command: Command) {
+        match (c.to_ascii_lowercase(), modifiers.ctrl, modifiers.alt) {
+            ('a', true, false) => self.edit(Input::move_to_start),
+            ('e', true, false) if !self.accept_suggestion(false) => self.edit(Input::move_to_end),
+            ('k', true, false) => self.kill(Input::kill_to_end, false, previous_command),
+            ('u', true, false) => self.kill(Input::kill_to_start, true, previous_command),
+            ('w', true, false) => self.kill(Input::kill_word_back, true, previous_command),
+            ('y', true, false) => self.yank(),
+            ('y', false, true) => self.yank_pop(previous_command),
+            ('_', true, false) => {
+                self.input.undo();
+                self.draw_input();
+            }
+            ('b', false, true) => self.edit(Input::word_back),
+            ('f', false, true) if !self.accept_suggestion(true) => self.edit(Input::word_forward),
+            ('l', true, false) => self.clear_screen(),
+            ('r', true, false) => {
+                self.search = Some(Search::default());
+                self.draw_input();
+            }
+            _ => (),
+        }
+    }
+
+    /// Keys while searching the history: typing refines the query, Ctrl+R finds an older
+    /// match and Esc or Ctrl+G cancels. Other keys take the match into the input and are
+    /// handled as usual, so Enter runs it. Returns whether the key was used by the search
+    fn search_event(&mut self, event: Event) -> bool {
+        let entries = self.history.entries();
+        let Some(search) = self.search.as_mut() else {
+            return false;
+        };
+        match event {
+            Event::ReceivedCharacter('\u{1b}') => self.end_search(false),
+            Event::Shortcut(c, modifiers) if modifiers.ctrl && c.eq_ignore_ascii_case(&'g') => {
+                self.end_search(false)
+            }
+            Event::Shortcut(c, modifiers) if modifiers.ctrl && c.eq_ignore_ascii_case(&'r') => {
+                search.older(entries);
+                self.draw_input();
+            }
+            Event::ReceivedCharacter(c) if !c.is_control() => {
+                search.push(c, entries);
+                self.draw_input();
+            }
+            Event::Backspace => {
+                search.pop(entries);
+                self.draw_input();
+            }
+            _ => {
+                self.end_search(true);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Puts the prompt back, with the entry found in the input if `accept`
+    fn end_search(&mut self, accept: bool) {
+        let Some(search) = self.search.take() else {
+            return;
+        };
+        let entry = search.entry(self.history.entries()).filter(|_| accept);
+        if let Some(line) =
+            entry.map(|(before, matched, after)| before.to_string() + matched + after)
+        {
+            self.input.edit(|input| input.set(&line));
+        }
+        let row = self.terminal.grid().screen_row(self.input_origin.0).unwrap_or(0);
+        self.terminal.perform(Action::CursorPosition { row, col: 0 });
+        self.terminal.perform(Action::EraseInDisplay(Erase::ToEnd));
+        self.print_prompt();
+        self.draw_input();
+    }
+
+    /// Applies an edit to the input that can be undone and redraws it
+    fn edit<T>(&mut self, edit: impl FnOnce(&mut Input) -> T) -> T {
+        let result = self.input.edit(edit);
+        self.draw_input();
+        result
+    }
+
+    /// Removes text from the input into the kill ring
+    fn kill(&mut self, kill: fn(&mut Input) -> String, backward: bool, previous: Command) {
+        let text = self.edit(kill);
+        self.kill_ring.kill(text, backward, previous == Command::Kill);
+        self.last_command = Command::Kill;
     }
 
-    fn shift_cursor(&mut self, shift: i32) {
-        self.cursor_pos = self.cursor_pos.saturating_add_signed(shift as isize);
-        if self.cursor_pos > self.input.len() {
-            self.cursor_pos = self.input.len();
+    /// Inserts the newest kill at the cursor
+    fn yank(&mut self) {
+        if let Some(text) = self.kill_ring.yank().map(str::to_string) {
+            self.edit(|input| input.insert_str(&text));
+            self.last_command = Command::Yank(text.graphemes(true).count());
         }
-        self.ui.shift_cursor(shift, self.input.len());
+    }
+
+    /// Replaces the text just yanked with the kill before it
+    fn yank_pop(&mut self, previous: Command) {
+        let Command::Yank(yanked) = previous else {
+            return;
+        };
+        if let Some(text) = self.kill_ring.yank_pop().map(str::to_string) {
+            self.edit(|input| {
+                input.erase_back(yanked);
+                input.insert_str(&text);
+            });
+            self.last_command = Command::Yank(text.graphemes(true).count());
+        }
+    }
+
+    /// Clears the screen and shows the prompt and the input at its top
+    fn clear_screen(&mut self) {
+        self.terminal.perform(Action::EraseInDisplay(Erase::All));
+        self.terminal.perform(Action::CursorPosition { row: 0, col: 0 });
+        self.print_prompt();
+        self.draw_input();
+    }
+
+    fn shift_cursor(&mut self, shift: isize) {
+        self.input.shift_cursor(shift);
+        self.draw_input();
     }
 
     fn move_cursor_to_end(&mut self) {
-        self.shift_cursor(self.input.len() as i32);
+        self.input.move_to_end();
+        self.draw_input();
     }
 
     fn previous_input(&mut self) {
@@ -110,52 +574,469 @@ impl Shell {
             return;
         }
         self.cmd_pointer -= 1;
-        self.input = self.cmd_history[self.cmd_pointer].clone();
-        self.draw_buffer();
-        self.move_cursor_to_end();
+        let line = self.history.entries()[self.cmd_pointer].clone();
+        self.edit(|input| input.set(&line));
     }
 
     fn next_input(&mut self) {
-        if self.cmd_pointer == self.cmd_history.len() - 1 {
+        if self.cmd_pointer + 1 >= self.history.entries().len() {
             return;
         }
         self.cmd_pointer += 1;
-        self.input = self.cmd_history[self.cmd_pointer].clone();
-        self.draw_buffer();
-        self.move_cursor_to_end();
+        let line = self.history.entries()[self.cmd_pointer].clone();
+        self.edit(|input| input.set(&line));
+    }
+
+    /// Runs a line of shell commands, or gives it to Python when it does not start with
+    /// a built-in or a program
+    fn process_cmd(&mut self, line: &str) {
+        let first = line.split_whitespace().next().unwrap_or_default();
+        let list = match parser::parse(line) {
+            Ok(list) => list,
+            // Python has its own quotes and operators
+            Err(_) if !self.is_shell_command(first) => return self.run_python(line),
+            Err(e) => {
+                self.print(&format!("{}\n", e));
+                self.status = 2;
+                return;
+            }
+        };
+        let word = list.first().and_then(|(_, pipeline)| pipeline[0].words.first());
+        // A quoted first word is a Python string
+        let quoted = line.trim_start().starts_with(['\'', '"']);
+        match word {
+            Some(word) if quoted || !self.is_shell_command(word) => self.run_python(line),
+            _ => {
+                self.queue = list.into();
+                self.run_queue();
+            }
+        }
     }
 
-    fn process_cmd(&self, input: &str) -> Vec<String> {
-        let mut output = vec![];
+    /// Whether `word` starting a command is run by the shell rather than Python. After
+    /// built-ins come Python names, then programs on $PATH and words that look like them
+    fn is_shell_command(&self, word: &str) -> bool {
+        if builtins::NAMES.contains(&word) {
+            return true;
+        }
+        let name_len = word.find(|c: char| !(c.is_alphanumeric() || c == '_'));
+        let (name, rest) = word.split_at(name_len.unwrap_or(word.len()));
+        // Python builtins share names with programs like `id` or `test`, they are only
+        // Python when called, used or assigned
+        let python_builtin = rest.starts_with(['(', '.', '=']) && processor::is_builtin(name);
+        if !name.is_empty() && (processor::defines(name) || python_builtin) {
+            return false;
+        }
+        job::find_program(word, self.dirs.cwd()).is_some() || is_command_name(word)
+    }
 
-        let mut input_splitted = input.split_whitespace();
-        if let Some(cmd) = input_splitted.next() {
-            let args = input_splitted;
-            dbg!(cmd);
-            match cmd {
-                "echo" => {
-                    output = vec![args.fold(String::new(), |r, c| r + c + " ")];
-                    output.push(String::new());
-                }
-                "ls" => {
-                    output = vec!["bin    dev    usr\n".to_string()];
-                    output.push(String::new());
+    fn run_python(&mut self, line: &str) {
+        let output = process(line, self.dirs.cwd());
+        self.print(&output.join("\n"));
+        self.status = 0;
+    }
+
+    /// Runs the pipelines of the line in turn until one of them starts a job
+    fn run_queue(&mut self) {
+        while self.job.is_none() {
+            let Some((connector, pipeline)) = self.queue.pop_front() else {
+                return;
+            };
+            let run = match connector {
+                Connector::Then => true,
+                Connector::And => self.status == 0,
+                Connector::Or => self.status != 0,
+            };
+            if run {
+                self.run_pipeline(pipeline);
+            }
+        }
+    }
+
+    fn run_pipeline(&mut self, pipeline: Pipeline) {
+        // A built-in on its own runs in the shell, so `cd` changes its directory
+        if let [command] = pipeline.as_slice() {
+            let name = command.words.first().map_or("", String::as_str);
+            if name.is_empty() || builtins::NAMES.contains(&name) {
+                self.status = self.run_builtin(command);
+                return;
+            }
+        }
+
+        let mut stages = vec![];
+        for command in pipeline {
+            let mut words = command.words.into_iter();
+            let name = words.next().unwrap_or_default();
+            let program = if name.is_empty() || builtins::NAMES.contains(&name.as_str()) {
+                Program::Builtin(name)
+            } else if let Some(path) = job::find_program(&name, self.dirs.cwd()) {
+                Program::External(path)
+            } else {
+                self.print(&format!("{}: command not found\n", name));
+                self.status = 127;
+                return;
+            };
+            stages.push(Stage {
+                program,
+                args: words.collect(),
+                redirections: command.redirections,
+            });
+        }
+        match Job::spawn(stages, &self.dirs) {
+            Ok(job) => self.job = Some(job),
+            Err(e) => {
+                self.print(&format!("{}\n", e));
+                self.status = 1;
+            }
+        }
+    }
+
+    /// Runs a built-in with its output on the screen unless it is redirected
+    fn run_builtin(&mut self, command: &parser::Command) -> i32 {
+        let streams = match Streams::default().redirect(&command.redirections, self.dirs.cwd()) {
+            Ok(streams) => streams,
+            Err(e) => {
+                self.print(&format!("{}\n", e));
+                return 1;
+            }
+        };
+        let input = streams.stdin.map(job::read_input);
+        let terminal = streams.stdout.is_none().then(|| self.terminal.grid().cols());
+        let (name, args) =
+            command.words.split_first().map_or(("", &[][..]), |(name, args)| (name.as_str(), args));
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = builtins::run(name, &args, &mut self.dirs, terminal, input.as_deref());
+        self.write(streams.stdout, &output.stdout);
+        self.write(streams.stderr, &output.stderr);
+        output.status()
+    }
+
+    /// Writes lines to a redirected stream or to the screen
+    fn write(&mut self, stream: Option<OwnedFd>, lines: &[String]) {
+        match stream {
+            Some(fd) => {
+                if let Err(e) = job::write_lines(fd, lines) {
+                    self.print(&format!("write error: {}\n", files::message(&e)));
                 }
-                _ => {
-                    output = process(input);
-                    // output = vec![format!("Command '{cmd}' not found"), String::new()];
+            }
+            None => {
+                for line in lines {
+                    self.print(&format!("{}\n", line));
                 }
             }
-        } else {
-            output.push(String::new());
         }
+    }
+}
+
+/// Whether `word` looks like the name of a program rather than a Python expression
+fn is_command_name(word: &str) -> bool {
+    word.starts_with(char::is_alphabetic)
+        && word.chars().all(|c| c.is_alphanumeric() || "-_.".contains(c))
+}
+
+/// Name of the user running the shell, from `$USER` or the password database
+fn username() -> String {
+    if let Some(user) = std::env::var("USER").ok().filter(|user| !user.is_empty()) {
+        return user;
+    }
+    files::user_name(unsafe { libc::getuid() })
+}
+
+/// Name of the machine up to its first dot, like `\h` in a bash prompt
+fn hostname() -> String {
+    let mut name = [0u8; 256];
+    if unsafe { libc::gethostname(name.as_mut_ptr().cast(), name.len()) } == -1 {
+        return "localhost".to_string();
+    }
+    let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    let name = String::from_utf8_lossy(&name[..len]);
+    name.split('.').next().unwrap_or_default().to_string()
+}
 
-        let username = "user".to_string();
-        let directory = "/".to_string();
-        let cwd = format!("{}:{}$ ", username, directory);
-        if let Some(last) = output.last_mut() {
-            last.push_str(&cwd);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::grid::{Flags, Grid};
+    use testing::TempDir;
+
+    /// Stands in for the window so the shell runs without a GPU
+    struct Headless;
+
+    impl View for Headless {
+        fn reload_config(&mut self, _: Result<&Config, &str>) {}
+        fn handle_event(&mut self, _: Event) {}
+        fn handle_mouse(&mut self, _: Event, _: &Grid) -> bool {
+            false
+        }
+        fn selection_text(&self, _: &Grid) -> Option<String> {
+            None
+        }
+        fn update_text(&mut self, _: &Grid) {}
+        fn update_cursor(&mut self, _: &Grid) {}
+        fn render(&mut self) {}
+        fn size_in_cells(&self) -> (usize, usize) {
+            (80, 24)
+        }
+    }
+
+    fn shell() -> Shell<Headless> {
+        Shell::new(Headless, 100, History::default()).unwrap()
+    }
+
+    fn typed(shell: &mut Shell<Headless>, text: &str) {
+        text.chars().for_each(|c| shell.handle_event(Event::ReceivedCharacter(c)));
+    }
+
+    fn ctrl(c: char) -> Event {
+        Event::Shortcut(c, Modifiers { ctrl: true, ..Modifiers::default() })
+    }
+
+    fn alt(c: char) -> Event {
+        Event::Shortcut(c, Modifiers { alt: true, ..Modifiers::default() })
+    }
+
+    #[test]
+    fn kills_are_yanked_back() {
+        let mut shell = shell();
+        typed(&mut shell, "echo one two three");
+        shell.handle_event(ctrl('w'));
+        shell.handle_event(ctrl('w'));
+        assert_eq!(shell.input.text(), "echo one ");
+        shell.handle_event(Event::Home(Modifiers::default()));
+        shell.handle_event(ctrl('k'));
+        assert_eq!(shell.input.text(), "");
+
+        shell.handle_event(ctrl('y'));
+        assert_eq!(shell.input.text(), "echo one ");
+        // Kills in a row were joined into one entry
+        shell.handle_event(alt('y'));
+        assert_eq!(shell.input.text(), "two three");
+        shell.handle_event(alt('y'));
+        assert_eq!(shell.input.text(), "echo one ");
+
+        // Alt+Y does nothing unless it follows a yank
+        shell.handle_event(Event::Release);
+        shell.handle_event(Event::Left);
+        shell.handle_event(alt('y'));
+        assert_eq!(shell.input.text(), "echo one ");
+    }
+
+    #[test]
+    fn undo_is_reset_on_enter() {
+        let mut shell = shell();
+        typed(&mut shell, "echo hi");
+        shell.handle_event(ctrl('u'));
+        shell.handle_event(ctrl('y'));
+        shell.handle_event(ctrl('y'));
+        assert_eq!(shell.input.text(), "echo hiecho hi");
+
+        shell.handle_event(ctrl('_'));
+        shell.handle_event(ctrl('_'));
+        assert_eq!(shell.input.text(), "");
+        shell.handle_event(ctrl('_'));
+        assert_eq!(shell.input.text(), "echo hi");
+        shell.handle_event(ctrl('_'));
+        assert_eq!(shell.input.text(), "echo h");
+
+        shell.handle_event(Event::Enter);
+        shell.handle_event(ctrl('_'));
+        assert_eq!(shell.input.text(), "");
+        // The kill ring outlives the line
+        shell.handle_event(ctrl('y'));
+        assert_eq!(shell.input.text(), "echo hi");
+    }
+
+    #[test]
+    fn reverse_search_takes_the_match_into_the_input() {
+        let mut shell = shell();
+        for line in ["echo one", "echo two", "echo three"] {
+            typed(&mut shell, line);
+            shell.handle_event(Event::Enter);
+        }
+        typed(&mut shell, "typed");
+        shell.handle_event(ctrl('r'));
+        typed(&mut shell, "o t");
+        let grid = shell.terminal.grid();
+        let row = grid.line(shell.input_origin.0).unwrap();
+        let text: String = row.cells.iter().map(|cell| cell.c).collect();
+        assert!(text.starts_with("(reverse-i-search)`o t': echo three"), "{}", text);
+        assert!(row.cells[28].flags.contains(Flags::INVERSE));
+        assert!(!row.cells[27].flags.contains(Flags::INVERSE));
+
+        // Esc leaves the input as it was
+        shell.handle_event(Event::ReceivedCharacter('\u{1b}'));
+        assert_eq!(shell.input.text(), "typed");
+
+        shell.handle_event(ctrl('r'));
+        typed(&mut shell, "o t");
+        shell.handle_event(ctrl('r'));
+        shell.handle_event(Event::End(Modifiers::default()));
+        assert_eq!(shell.input.text(), "echo two");
+        shell.handle_event(ctrl('_'));
+        assert_eq!(shell.input.text(), "typed");
+    }
+
+    #[test]
+    fn suggestion_is_dim_and_accepted() {
+        let mut shell = shell();
+        typed(&mut shell, "echo commit -m wip");
+        shell.handle_event(Event::Enter);
+        wait_for_job(&mut shell);
+        typed(&mut shell, "echo");
+        let (row, col) = shell.input_origin;
+        let cells = &shell.terminal.grid().line(row).unwrap().cells;
+        let suggested: String = cells[col + 4..col + 18].iter().map(|cell| cell.c).collect();
+        assert_eq!(suggested, " commit -m wip");
+        assert!(cells[col + 4].flags.contains(Flags::DIM));
+        assert!(!cells[col + 3].flags.contains(Flags::DIM));
+
+        shell.handle_event(alt('f'));
+        assert_eq!(shell.input.text(), "echo commit");
+        shell.handle_event(Event::Right);
+        assert_eq!(shell.input.text(), "echo commit -m wip");
+
+        // Nothing is suggested away from the end of the input
+        shell.handle_event(Event::Left);
+        shell.handle_event(Event::Backspace);
+        assert_eq!(shell.suggestion(), None);
+        shell.handle_event(Event::End(Modifiers::default()));
+        assert_eq!(shell.input.text(), "echo commit -m wp");
+        assert_eq!(shell.suggestion(), None);
+
+        // Only the input is left on the line that runs
+        shell.handle_event(Event::Home(Modifiers::default()));
+        shell.handle_event(ctrl('k'));
+        typed(&mut shell, "echo");
+        shell.handle_event(Event::Enter);
+        wait_for_job(&mut shell);
+        let cells = &shell.terminal.grid().line(row).unwrap().cells;
+        assert!(cells[col + 4..].iter().all(|cell| cell.c == ' '));
+    }
+
+    #[test]
+    fn tab_completes_and_lists_candidates() {
+        let mut shell = shell();
+        typed(&mut shell, "ec");
+        shell.handle_event(Event::ReceivedCharacter('\t'));
+        assert_eq!(shell.input.text(), "echo ");
+
+        shell.handle_event(ctrl('u'));
+        typed(&mut shell, "ev");
+        shell.handle_event(Event::Left);
+        let row = shell.input_origin.0;
+        shell.handle_event(Event::ReceivedCharacter('\t'));
+        assert_eq!(shell.input.text(), "ev");
+        let grid = shell.terminal.grid();
+        let listing: String = grid.line(row + 1).unwrap().cells.iter().map(|cell| cell.c).collect();
+        // Only the word before the cursor is completed
+        assert!(listing.starts_with("echo") && listing.contains("eval"), "{}", listing);
+        // The input is drawn again under the listing with the cursor where it was
+        assert!(shell.input_origin.0 > row + 1);
+        assert_eq!(shell.input.split(), ("e", "v"));
+
+        // A completed path is escaped so it stays one word
+        let dir = TempDir::new("tab");
+        std::fs::write(dir.join("my notes.txt"), "notes").unwrap();
+        shell.handle_event(Event::End(Modifiers::default()));
+        shell.handle_event(ctrl('u'));
+        run(&mut shell, &format!("cd '{}'", dir.display()));
+        typed(&mut shell, "cat 'my no");
+        shell.handle_event(Event::ReceivedCharacter('\t'));
+        assert_eq!(shell.input.text(), "cat my\\ notes.txt ");
+        assert_eq!(run(&mut shell, ""), "notes");
+    }
+
+    fn run(shell: &mut Shell<Headless>, line: &str) -> String {
+        typed(shell, line);
+        // Output starts below the last row of the input, which can wrap
+        let grid = shell.terminal.grid();
+        let row = grid.absolute_row(grid.cursor().row);
+        shell.handle_event(Event::Enter);
+        wait_for_job(shell);
+        let grid = shell.terminal.grid();
+        (row + 1..shell.input_origin.0)
+            .filter_map(|line| grid.line(line))
+            .map(|row| {
+                row.cells.iter().map(|cell| cell.c).collect::<String>().trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn cd_moves_the_prompt_and_python() {
+        let dir = TempDir::new("cd");
+        let mut shell = shell();
+        let started_in = shell.dirs.cwd().to_path_buf();
+
+        assert_eq!(run(&mut shell, &format!("cd {}", dir.display())), "");
+        let grid = shell.terminal.grid();
+        let prompt: String =
+            grid.line(shell.input_origin.0).unwrap().cells.iter().map(|c| c.c).collect();
+        assert!(prompt.trim_end().ends_with(&format!(":{}$", dir.display())), "{}", prompt);
+        assert_eq!(run(&mut shell, "pwd"), dir.display().to_string());
+        assert_eq!(run(&mut shell, "__import__('os').getcwd()"), dir.display().to_string());
+        assert_eq!(run(&mut shell, "cd missing"), "cd: missing: No such file or directory");
+
+        assert_eq!(run(&mut shell, "cd -"), directories::display(&started_in));
+        assert_eq!(shell.dirs.cwd(), started_in);
+    }
+
+    fn wait_for_job(shell: &mut Shell<Headless>) {
+        let started = std::time::Instant::now();
+        while shell.job.is_some() {
+            assert!(started.elapsed() < std::time::Duration::from_secs(5), "program still running");
+            shell.update();
+            std::thread::sleep(std::time::Duration::from_millis(1));
         }
-        output
+    }
+
+    #[test]
+    fn programs_run_after_builtins_and_python() {
+        let mut shell = shell();
+        assert_eq!(run(&mut shell, "sh -c 'echo hello'"), "hello");
+        assert_eq!(run(&mut shell, "test()"), "test");
+        assert_eq!(run(&mut shell, "no-such-program status"), "no-such-program: command not found");
+        // Python builtins are Python when they are called
+        assert_eq!(run(&mut shell, "id(None) == id(None)"), "True");
+
+        let dir = TempDir::new("programs");
+        run(&mut shell, &format!("cd {}", dir.display()));
+        assert_eq!(run(&mut shell, "sh -c pwd"), dir.display().to_string());
+
+        // Ctrl+C stops the program, what is typed meanwhile is dropped
+        typed(&mut shell, "sh -c 'while :; do :; done'");
+        shell.handle_event(Event::Enter);
+        typed(&mut shell, "ls");
+        shell.handle_event(ctrl('c'));
+        wait_for_job(&mut shell);
+        assert_eq!(shell.input.text(), "");
+    }
+
+    #[test]
+    fn lines_are_parsed_into_pipelines_and_lists() {
+        let dir = TempDir::new("line");
+        let mut shell = shell();
+        run(&mut shell, &format!("cd '{}'", dir.display()));
+
+        assert_eq!(run(&mut shell, r#"echo "a  b" 'c  d'\ e"#), "a  b c  d e");
+        assert_eq!(
+            run(&mut shell, "cat missing && echo no || echo yes; echo $"),
+            "cat: missing: No such file or directory\nyes\n$"
+        );
+        assert_eq!(run(&mut shell, r#"echo hi | sh -c 'read x; echo "<$x>"'"#), "<hi>");
+        assert_eq!(run(&mut shell, "echo hi > f; echo yo >> f; cat < f"), "hi\nyo");
+        assert_eq!(
+            run(&mut shell, "ls missing 2>&1 | cat"),
+            "ls: cannot access 'missing': No such file or directory"
+        );
+        assert_eq!(run(&mut shell, "echo a |"), "syntax error: unexpected end of line");
+        assert_eq!(
+            run(&mut shell, "no-such-program || echo gone"),
+            "no-such-program: command not found\ngone"
+        );
+        // Python keeps its own quotes and operators
+        assert_eq!(run(&mut shell, "'a' + \"b\""), "ab");
+        assert_eq!(run(&mut shell, "1 | 2"), "3");
     }
 }
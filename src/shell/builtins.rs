@@ -0,0 +1,102 @@
+use super::{
+    directories::{self, Directories},
+    files,
+};
+
+/// Commands run by the shell itself rather than Python or a program
+pub const NAMES: &[&str] =
+    &["cat", "cd", "cp", "echo", "ls", "mkdir", "mv", "popd", "pushd", "pwd", "rm", "touch"];
+
+/// Lines a built-in wrote to its standard output and error
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Output {
+    pub stdout: Vec<String>,
+    pub stderr: Vec<String>,
+}
+
+impl Output {
+    pub fn stdout(lines: Vec<String>) -> Output {
+        Output { stdout: lines, stderr: vec![] }
+    }
+
+    pub fn error(message: String) -> Output {
+        Output { stdout: vec![], stderr: vec![message] }
+    }
+
+    /// Exit status, a built-in fails when it reported an error
+    pub fn status(&self) -> i32 {
+        if self.stderr.is_empty() {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+/// Runs the built-in `name`. `input` is what was piped or redirected into it and
+/// `terminal` the width of the screen when the output goes there
+pub fn run(
+    name: &str,
+    args: &[&str],
+    dirs: &mut Directories,
+    terminal: Option<usize>,
+    input: Option<&str>,
+) -> Output {
+    let cwd = dirs.cwd();
+    match name {
+        "echo" => Output::stdout(vec![args.join(" ")]),
+        "pwd" => Output::stdout(vec![cwd.display().to_string()]),
+        "ls" => files::ls(args, cwd, terminal),
+        "cat" => files::cat(args, cwd, input),
+        "mkdir" => files::mkdir(args, cwd),
+        "rm" => files::rm(args, cwd),
+        "cp" => files::cp(args, cwd),
+        "mv" => files::mv(args, cwd),
+        "touch" => files::touch(args, cwd),
+        "cd" => {
+            let arg = args.first().copied();
+            match dirs.cd(arg) {
+                // Like other shells `cd -` tells where it went
+                Ok(()) if arg == Some("-") => {
+                    Output::stdout(vec![directories::display(dirs.cwd())])
+                }
+                Ok(()) => Output::default(),
+                Err(e) => Output::error(format!("cd: {}", e)),
+            }
+        }
+        "pushd" | "popd" => {
+            let result =
+                if name == "pushd" { dirs.pushd(args.first().copied()) } else { dirs.popd() };
+            match result {
+                Ok(()) => Output::stdout(vec![dirs.stack()]),
+                Err(e) => Output::error(format!("{}: {}", name, e)),
+            }
+        }
+        // A command of only redirections creates its files and runs nothing
+        "" => Output::default(),
+        _ => Output::error(format!("{}: not a built-in", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn echo_joins_its_arguments() {
+        let mut dirs = Directories::new(env::temp_dir());
+        let output = run("echo", &["a  b", "c"], &mut dirs, None, None);
+        assert_eq!(output, Output::stdout(vec!["a  b c".to_string()]));
+        assert_eq!(output.status(), 0);
+    }
+
+    #[test]
+    fn errors_go_to_stderr() {
+        let mut dirs = Directories::new(env::temp_dir());
+        let output = run("cd", &["no such dir"], &mut dirs, None, None);
+        assert!(output.stdout.is_empty());
+        assert_eq!(output.stderr, ["cd: no such dir: No such file or directory"]);
+        assert_eq!(output.status(), 1);
+    }
+}
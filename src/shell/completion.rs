@@ -0,0 +1,209 @@
+use std::{fs, path::Path};
+
+use super::{directories::expand_tilde, parser};
+
+/// Ways the word before the cursor can be completed
+#[derive(Debug, Default, PartialEq)]
+pub struct Completion {
+    /// Byte offset in the line where the completed word starts
+    pub start: usize,
+    pub candidates: Vec<Candidate>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Candidate {
+    /// Replaces the word, a path is escaped so it is read back as one word
+    pub text: String,
+    /// Shown in the listing of candidates, the file name of a path or the attribute of a
+    /// Python name
+    pub display: String,
+}
+
+impl Completion {
+    /// Longest text all candidates start with, without a backslash escaping nothing
+    pub fn common_prefix(&self) -> String {
+        let mut candidates = self.candidates.iter().map(|candidate| candidate.text.as_str());
+        let first = candidates.next().unwrap_or_default();
+        let len = candidates.fold(first.len(), |len, text| {
+            first[..len]
+                .char_indices()
+                .zip(text.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(len.min(text.len()), |((i, _), _)| i)
+        });
+        let prefix = &first[..len];
+        let backslashes = prefix.len() - prefix.trim_end_matches('\\').len();
+        prefix[..len - backslashes % 2].to_string()
+    }
+}
+
+/// Completes the word at the end of `line`: the first word with built-in commands and
+/// Python names, the others and anything with a `/` with paths relative to `cwd`.
+/// Paths are words of the shell with its quotes, Python names are split at whitespace.
+/// `python` lists the Python names starting with a word like `os.pa`
+pub fn complete(
+    line: &str,
+    builtins: &[&str],
+    cwd: &Path,
+    python: impl Fn(&str) -> Vec<String>,
+) -> Completion {
+    let (start, word) = parser::last_word(line);
+    if !line[..start].trim().is_empty() || word.contains('/') {
+        return Completion { start, candidates: paths(&word, cwd) };
+    }
+
+    let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    let word = &line[start..];
+
+    let mut candidates: Vec<Candidate> = builtins
+        .iter()
+        .filter(|builtin| builtin.starts_with(word))
+        .map(|builtin| Candidate { text: builtin.to_string(), display: builtin.to_string() })
+        .collect();
+
+    // A Python expression like `print(os.pa` completes its last dotted name
+    let name_start =
+        word.rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.')).map_or(0, |i| i + 1);
+    let name = &word[name_start..];
+    let leading = &word[..name_start];
+    candidates.extend(python(name).into_iter().map(|name| {
+        let display = name.rsplit('.').next().unwrap_or_default().to_string();
+        Candidate { text: format!("{}{}", leading, name), display }
+    }));
+    candidates.sort();
+    candidates.dedup();
+    Completion { start, candidates }
+}
+
+/// Entries of the directory of `word` whose names start with its last part, directories
+/// end with `/`. Hidden entries are only listed for a name starting with `.`, a leading
+/// `~` is the home directory. `word` has its quotes removed
+fn paths(word: &str, cwd: &Path) -> Vec<Candidate> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => word.split_at(i + 1),
+        None => ("", word),
+    };
+    let Ok(entries) = fs::read_dir(cwd.join(expand_tilde(dir))) else {
+        return vec![];
+    };
+    let mut candidates: Vec<Candidate> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let mut name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            // Followed through symlinks so a link to a directory completes like one
+            if entry.path().is_dir() {
+                name.push('/');
+            }
+            Some(Candidate { text: parser::escape(&format!("{}{}", dir, name)), display: name })
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::testing::TempDir;
+
+    const BUILTINS: &[&str] = &["echo", "exit", "ls"];
+
+    fn no_python(_: &str) -> Vec<String> {
+        vec![]
+    }
+
+    fn texts(completion: &Completion) -> Vec<&str> {
+        completion.candidates.iter().map(|candidate| candidate.text.as_str()).collect()
+    }
+
+    fn temp_dir() -> TempDir {
+        let dir = TempDir::new("completion");
+        for path in ["src/main.rs", "src/shell.rs", "setup.py", ".hidden"] {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn first_word_completes_commands_and_python_names() {
+        let cwd = Path::new("/");
+        let completion = complete("e", BUILTINS, cwd, no_python);
+        assert_eq!(texts(&completion), ["echo", "exit"]);
+        assert_eq!(completion.common_prefix(), "e");
+
+        let python = |name: &str| match name {
+            "os.pa" => vec!["os.pardir".to_string(), "os.path".to_string()],
+            _ => vec![],
+        };
+        let completion = complete("  print(os.pa", BUILTINS, cwd, python);
+        assert_eq!(completion.start, 2);
+        assert_eq!(texts(&completion), ["print(os.pardir", "print(os.path"]);
+        assert_eq!(completion.candidates[1].display, "path");
+        assert_eq!(completion.common_prefix(), "print(os.pa");
+    }
+
+    #[test]
+    fn arguments_complete_paths() {
+        let cwd = temp_dir();
+        let completion = complete("cat s", BUILTINS, &cwd, no_python);
+        assert_eq!(completion.start, 4);
+        assert_eq!(texts(&completion), ["setup.py", "src/"]);
+        assert_eq!(completion.common_prefix(), "s");
+
+        let completion = complete("cat src/s", BUILTINS, &cwd, no_python);
+        assert_eq!(texts(&completion), ["src/shell.rs"]);
+        assert_eq!(completion.candidates[0].display, "shell.rs");
+
+        let completion = complete("ls ", BUILTINS, &cwd, no_python);
+        assert_eq!(texts(&completion), ["setup.py", "src/"]);
+        let completion = complete("ls .", BUILTINS, &cwd, no_python);
+        assert_eq!(texts(&completion), [".hidden"]);
+
+        // Paths complete in place of a command too
+        let completion = complete("./s", BUILTINS, &cwd, no_python);
+        assert_eq!(texts(&completion), ["./setup.py", "./src/"]);
+    }
+
+    #[test]
+    fn paths_follow_the_quotes_of_the_shell() {
+        let cwd = temp_dir();
+        fs::write(cwd.join("my notes.txt"), "").unwrap();
+        fs::write(cwd.join("my list.txt"), "").unwrap();
+
+        let completion = complete("cat my\\ no", BUILTINS, &cwd, no_python);
+        assert_eq!(completion.start, 4);
+        assert_eq!(texts(&completion), ["my\\ notes.txt"]);
+        assert_eq!(completion.candidates[0].display, "my notes.txt");
+
+        let completion = complete("cat 'my no", BUILTINS, &cwd, no_python);
+        assert_eq!(completion.start, 4);
+        assert_eq!(texts(&completion), ["my\\ notes.txt"]);
+
+        let completion = complete("ls \"my", BUILTINS, &cwd, no_python);
+        assert_eq!(texts(&completion), ["my\\ list.txt", "my\\ notes.txt"]);
+        assert_eq!(completion.common_prefix(), "my\\ ");
+        // Operators end a word like whitespace
+        let completion = complete("echo a|cat my", BUILTINS, &cwd, no_python);
+        assert_eq!(completion.start, 11);
+    }
+
+    #[test]
+    fn common_prefix_stops_at_a_character() {
+        let candidate = |text: &str| Candidate { text: text.into(), display: text.into() };
+        let completion =
+            Completion { start: 0, candidates: vec![candidate("café"), candidate("cafè")] };
+        assert_eq!(completion.common_prefix(), "caf");
+        let completion = Completion { start: 0, candidates: vec![candidate("src/main.rs")] };
+        assert_eq!(completion.common_prefix(), "src/main.rs");
+        assert_eq!(Completion::default().common_prefix(), "");
+        // A backslash is kept with the character it escapes
+        let completion =
+            Completion { start: 0, candidates: vec![candidate("a\\ b"), candidate("a\\|b")] };
+        assert_eq!(completion.common_prefix(), "a");
+    }
+}
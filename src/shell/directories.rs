@@ -0,0 +1,212 @@
+use std::{
+    env, io,
+    path::{Component, Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use super::files;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{path}: {}", files::message(.inner))]
+    Access {
+        path: String,
+        #[source]
+        inner: io::Error,
+    },
+    #[error("{0}: Not a directory")]
+    NotADirectory(String),
+    #[error("HOME not set")]
+    NoHome,
+    #[error("OLDPWD not set")]
+    NoPrevious,
+    #[error("directory stack empty")]
+    EmptyStack,
+}
+
+/// Current directory of the shell, the one before it for `cd -` and the stack of
+/// `pushd` and `popd`. Paths are kept as typed, `..` goes back up a symlink
+#[derive(Clone, Debug)]
+pub struct Directories {
+    cwd: PathBuf,
+    previous: Option<PathBuf>,
+    /// Directories pushed by `pushd`, the newest last
+    stack: Vec<PathBuf>,
+}
+
+impl Directories {
+    pub fn new(cwd: PathBuf) -> Directories {
+        Directories { cwd, previous: None, stack: vec![] }
+    }
+
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    /// Changes to `arg`, the home directory without one or the previous directory for `-`
+    pub fn cd(&mut self, arg: Option<&str>) -> Result<(), Error> {
+        let path = match arg {
+            None => home().ok_or(Error::NoHome)?,
+            Some("-") => self.previous.clone().ok_or(Error::NoPrevious)?,
+            Some(arg) => self.resolve(arg),
+        };
+        self.change_to(path, arg.unwrap_or("~"))
+    }
+
+    /// Pushes the current directory and changes to `arg`, or swaps the two directories on
+    /// top without one
+    pub fn pushd(&mut self, arg: Option<&str>) -> Result<(), Error> {
+        let path = match arg {
+            Some(arg) => self.resolve(arg),
+            None => self.stack.last().cloned().ok_or(Error::EmptyStack)?,
+        };
+        let cwd = self.cwd.clone();
+        self.change_to(path, arg.unwrap_or("~"))?;
+        if arg.is_none() {
+            self.stack.pop();
+        }
+        self.stack.push(cwd);
+        Ok(())
+    }
+
+    /// Changes back to the directory pushed last
+    pub fn popd(&mut self) -> Result<(), Error> {
+        let path = self.stack.last().cloned().ok_or(Error::EmptyStack)?;
+        let arg = display(&path);
+        self.change_to(path, &arg)?;
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// The current directory and the stack from the newest, like `dirs`
+    pub fn stack(&self) -> String {
+        let dirs = std::iter::once(&self.cwd).chain(self.stack.iter().rev());
+        dirs.map(|dir| display(dir)).collect::<Vec<_>>().join(" ")
+    }
+
+    /// `arg` relative to the current directory, with `.` and `..` removed
+    pub fn resolve(&self, arg: &str) -> PathBuf {
+        normalize(&self.cwd.join(arg))
+    }
+
+    fn change_to(&mut self, path: PathBuf, arg: &str) -> Result<(), Error> {
+        let metadata =
+            path.metadata().map_err(|inner| Error::Access { path: arg.to_string(), inner })?;
+        if !metadata.is_dir() {
+            return Err(Error::NotADirectory(arg.to_string()));
+        }
+        self.previous = Some(std::mem::replace(&mut self.cwd, path));
+        Ok(())
+    }
+}
+
+pub fn home() -> Option<PathBuf> {
+    env::var_os("HOME").filter(|home| !home.is_empty()).map(PathBuf::from)
+}
+
+/// `path` with the home directory written as `~`
+pub fn display(path: &Path) -> String {
+    match home().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// Replaces a leading `~` by the home directory
+pub fn expand_tilde(path: &str) -> PathBuf {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest.trim_start_matches('/'),
+        _ => return PathBuf::from(path),
+    };
+    match home() {
+        Some(home) => home.join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Removes `.` and goes up for `..` without following symlinks, like `cd` in a shell
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::testing::TempDir;
+    use std::fs;
+
+    fn temp_dir() -> TempDir {
+        let dir = TempDir::new("directories");
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::write(dir.join("file"), "").unwrap();
+        dir
+    }
+
+    #[test]
+    fn cd_and_back() {
+        let root = temp_dir();
+        let mut dirs = Directories::new(root.to_path_buf());
+        assert!(matches!(dirs.cd(Some("-")), Err(Error::NoPrevious)));
+
+        dirs.cd(Some("a/./b")).unwrap();
+        assert_eq!(dirs.cwd(), root.join("a/b"));
+        dirs.cd(Some("../..")).unwrap();
+        assert_eq!(dirs.cwd(), &*root);
+        dirs.cd(Some("-")).unwrap();
+        assert_eq!(dirs.cwd(), root.join("a/b"));
+
+        let error = dirs.cd(Some("missing")).unwrap_err().to_string();
+        assert_eq!(error, "missing: No such file or directory");
+        let error = dirs.cd(Some("../../file")).unwrap_err().to_string();
+        assert_eq!(error, "../../file: Not a directory");
+        assert_eq!(dirs.cwd(), root.join("a/b"));
+    }
+
+    #[test]
+    fn pushd_and_popd() {
+        let root = temp_dir();
+        let mut dirs = Directories::new(root.to_path_buf());
+        assert!(matches!(dirs.popd(), Err(Error::EmptyStack)));
+
+        dirs.pushd(Some("a")).unwrap();
+        dirs.pushd(Some("b")).unwrap();
+        assert_eq!(dirs.cwd(), root.join("a/b"));
+        assert_eq!(dirs.stack.len(), 2);
+
+        // Without a directory the two on top are swapped
+        dirs.pushd(None).unwrap();
+        assert_eq!(dirs.cwd(), root.join("a"));
+        assert_eq!(dirs.stack, [root.to_path_buf(), root.join("a/b")]);
+
+        dirs.popd().unwrap();
+        assert_eq!(dirs.cwd(), root.join("a/b"));
+        dirs.popd().unwrap();
+        assert_eq!(dirs.cwd(), &*root);
+        assert!(dirs.stack.is_empty());
+    }
+
+    #[test]
+    fn home_is_written_as_tilde() {
+        let Some(home) = home() else {
+            return;
+        };
+        assert_eq!(expand_tilde("~"), home);
+        assert_eq!(expand_tilde("~/src"), home.join("src"));
+        assert_eq!(expand_tilde("~user"), PathBuf::from("~user"));
+        assert_eq!(display(&home), "~");
+        assert_eq!(display(&home.join("src")), "~/src");
+        assert_eq!(display(Path::new("/")), "/");
+    }
+}
@@ -0,0 +1,631 @@
+use std::{
+    ffi::CStr,
+    fs::{self, File, Metadata},
+    io,
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{builtins::Output, layout};
+
+/// Bold blue, what `ls` colors directories with
+const DIRECTORY_COLOR: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+/// Seconds after which `ls -l` shows the year instead of the time, about half a year
+const RECENT: i64 = 60 * 60 * 24 * 365 / 2;
+
+/// Lists directories, or shows files, given in `args`. `-a` shows hidden entries and
+/// `-l` one entry per line with its mode, owner, size and time. On the `terminal`, names
+/// are put in columns fitting its width and directories are colored, otherwise there is
+/// one per line
+pub fn ls(args: &[&str], cwd: &Path, terminal: Option<usize>) -> Output {
+    let (options, mut operands) = match options("ls", args, "al") {
+        Ok(parsed) => parsed,
+        Err(e) => return Output::error(e),
+    };
+    let (all, long) = (options.contains('a'), options.contains('l'));
+    if operands.is_empty() {
+        operands.push(".");
+    }
+
+    let mut output = Output::default();
+    let mut files = vec![];
+    let mut dirs = vec![];
+    for operand in &operands {
+        let path = resolve(cwd, operand);
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => dirs.push((operand, path)),
+            Ok(_) => files.push(Entry::new(operand.to_string(), path)),
+            Err(e) => {
+                output.stderr.push(format!("ls: cannot access '{}': {}", operand, message(&e)))
+            }
+        }
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    if !files.is_empty() {
+        output.stdout.extend(listing(&files, long, terminal));
+    }
+
+    for (i, (operand, path)) in dirs.iter().enumerate() {
+        if operands.len() > 1 {
+            if i > 0 || !files.is_empty() {
+                output.stdout.push(String::new());
+            }
+            output.stdout.push(format!("{}:", operand));
+        }
+        match entries(path, all) {
+            Ok(entries) => {
+                if long {
+                    let blocks: u64 = entries.iter().map(|entry| entry.blocks()).sum();
+                    output.stdout.push(format!("total {}", blocks));
+                }
+                output.stdout.extend(listing(&entries, long, terminal));
+            }
+            Err(e) => output.stderr.push(format!(
+                "ls: cannot open directory '{}': {}",
+                operand,
+                message(&e)
+            )),
+        }
+    }
+    output
+}
+
+/// Prints the contents of the files one after another, or `input` without files or
+/// for `-`
+pub fn cat(args: &[&str], cwd: &Path, input: Option<&str>) -> Output {
+    let mut text = String::new();
+    let mut stderr = vec![];
+    let args = if args.is_empty() { &["-"] } else { args };
+    for arg in args {
+        match *arg {
+            "-" => text.push_str(input.unwrap_or_default()),
+            arg => match fs::read(resolve(cwd, arg)) {
+                Ok(bytes) => text.push_str(&String::from_utf8_lossy(&bytes)),
+                Err(e) => stderr.push(format!("cat: {}: {}", arg, message(&e))),
+            },
+        }
+    }
+    Output { stdout: text.lines().map(str::to_string).collect(), stderr }
+}
+
+/// Creates directories, `-p` creates their parents too and accepts existing ones
+pub fn mkdir(args: &[&str], cwd: &Path) -> Output {
+    let (options, operands) = match options("mkdir", args, "p") {
+        Ok(parsed) => parsed,
+        Err(e) => return Output::error(e),
+    };
+    if operands.is_empty() {
+        return Output::error("mkdir: missing operand".to_string());
+    }
+    let stderr = operands
+        .iter()
+        .filter_map(|operand| {
+            let path = resolve(cwd, operand);
+            let result =
+                if options.contains('p') { fs::create_dir_all(path) } else { fs::create_dir(path) };
+            result
+                .err()
+                .map(|e| format!("mkdir: cannot create directory '{}': {}", operand, message(&e)))
+        })
+        .collect();
+    Output { stdout: vec![], stderr }
+}
+
+/// Removes files, `-r` removes directories with their contents and `-f` ignores missing
+/// files
+pub fn rm(args: &[&str], cwd: &Path) -> Output {
+    let (options, operands) = match options("rm", args, "rf") {
+        Ok(parsed) => parsed,
+        Err(e) => return Output::error(e),
+    };
+    let (recursive, force) = (options.contains('r'), options.contains('f'));
+    if operands.is_empty() && !force {
+        return Output::error("rm: missing operand".to_string());
+    }
+    let stderr = operands
+        .iter()
+        .filter_map(|operand| {
+            if matches!(operand.trim_end_matches('/').rsplit('/').next(), Some("." | "..")) {
+                return Some(format!(
+                    "rm: refusing to remove '.' or '..' directory: skipping '{}'",
+                    operand
+                ));
+            }
+            let path = resolve(cwd, operand);
+            let result = match fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.is_dir() && recursive => fs::remove_dir_all(path),
+                Ok(metadata) if metadata.is_dir() => {
+                    return Some(format!("rm: cannot remove '{}': Is a directory", operand))
+                }
+                Ok(_) => fs::remove_file(path),
+                Err(e) if force && e.kind() == io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e),
+            };
+            result.err().map(|e| format!("rm: cannot remove '{}': {}", operand, message(&e)))
+        })
+        .collect();
+    Output { stdout: vec![], stderr }
+}
+
+/// Copies a file to another, or files into a directory. `-r` copies directories
+pub fn cp(args: &[&str], cwd: &Path) -> Output {
+    let (options, operands) = match options("cp", args, "r") {
+        Ok(parsed) => parsed,
+        Err(e) => return Output::error(e),
+    };
+    let recursive = options.contains('r');
+    transfer("cp", &operands, cwd, |operand, target, from, to| {
+        if from.is_dir() && !recursive {
+            return Err(format!("cp: -r not specified; omitting directory '{}'", operand));
+        }
+        // The copy would be read again while it is being written
+        if from.is_dir() && is_inside(to, from) {
+            return Err(format!(
+                "cp: cannot copy a directory, '{}', into itself, '{}'",
+                operand, target
+            ));
+        }
+        copy(from, to).map_err(|e| format!("cp: cannot copy '{}': {}", operand, message(&e)))
+    })
+}
+
+/// Renames a file, or moves files into a directory
+pub fn mv(args: &[&str], cwd: &Path) -> Output {
+    let (_, operands) = match options("mv", args, "") {
+        Ok(parsed) => parsed,
+        Err(e) => return Output::error(e),
+    };
+    transfer("mv", &operands, cwd, |operand, target, from, to| {
+        if from.is_dir() && is_inside(to, from) {
+            return Err(format!(
+                "mv: cannot move '{}' to a subdirectory of itself, '{}'",
+                operand, target
+            ));
+        }
+        let result = match fs::rename(from, to) {
+            // A rename cannot cross filesystems, the file is copied and the source removed
+            Err(e) if e.raw_os_error() == Some(libc::EXDEV) => copy(from, to).and_then(|()| {
+                if from.is_dir() {
+                    fs::remove_dir_all(from)
+                } else {
+                    fs::remove_file(from)
+                }
+            }),
+            result => result,
+        };
+        result.map_err(|e| format!("mv: cannot move '{}': {}", operand, message(&e)))
+    })
+}
+
+/// Creates empty files, or sets the modification time of existing ones to now
+pub fn touch(args: &[&str], cwd: &Path) -> Output {
+    if args.is_empty() {
+        return Output::error("touch: missing file operand".to_string());
+    }
+    let stderr = args
+        .iter()
+        .filter_map(|arg| {
+            let result = File::options()
+                .create(true)
+                .append(true)
+                .open(resolve(cwd, arg))
+                .and_then(|file| file.set_modified(SystemTime::now()));
+            result.err().map(|e| format!("touch: cannot touch '{}': {}", arg, message(&e)))
+        })
+        .collect();
+    Output { stdout: vec![], stderr }
+}
+
+/// Name of the user with `uid`, or the number when it has none
+pub fn user_name(uid: u32) -> String {
+    let passwd = unsafe { libc::getpwuid(uid) };
+    if passwd.is_null() {
+        return uid.to_string();
+    }
+    unsafe { CStr::from_ptr((*passwd).pw_name) }.to_string_lossy().into_owned()
+}
+
+fn group_name(gid: u32) -> String {
+    let group = unsafe { libc::getgrgid(gid) };
+    if group.is_null() {
+        return gid.to_string();
+    }
+    unsafe { CStr::from_ptr((*group).gr_name) }.to_string_lossy().into_owned()
+}
+
+/// A file listed by `ls`, with the metadata of a symlink itself rather than its target
+struct Entry {
+    name: String,
+    path: PathBuf,
+    metadata: Option<Metadata>,
+}
+
+impl Entry {
+    fn new(name: String, path: PathBuf) -> Entry {
+        let metadata = fs::symlink_metadata(&path).ok();
+        Entry { name, path, metadata }
+    }
+
+    fn is_dir(&self) -> bool {
+        self.metadata.as_ref().is_some_and(Metadata::is_dir)
+    }
+
+    /// Disk usage in the 1024 byte blocks of `ls`
+    fn blocks(&self) -> u64 {
+        self.metadata.as_ref().map_or(0, |metadata| metadata.blocks() / 2)
+    }
+
+    /// The name, colored for a directory
+    fn colored_name(&self) -> String {
+        if self.is_dir() {
+            format!("{}{}{}", DIRECTORY_COLOR, self.name, RESET)
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+/// Entries of `dir` sorted by name, with `.` and `..` and hidden files if `all`
+fn entries(dir: &Path, all: bool) -> io::Result<Vec<Entry>> {
+    let mut entries = vec![];
+    if all {
+        entries.push(Entry::new(".".to_string(), dir.to_path_buf()));
+        entries.push(Entry::new("..".to_string(), dir.join("..")));
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if all || !name.starts_with('.') {
+            entries.push(Entry::new(name, entry.path()));
+        }
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+fn listing(entries: &[Entry], long: bool, terminal: Option<usize>) -> Vec<String> {
+    let name = |entry: &Entry| match terminal {
+        Some(_) => entry.colored_name(),
+        None => entry.name.clone(),
+    };
+    if !long {
+        let names: Vec<String> = entries.iter().map(name).collect();
+        // Without a terminal every name is on its own line for the next program
+        let width = terminal.unwrap_or(0);
+        return layout::columns(&names, width).lines().map(str::to_string).collect();
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs() as i64);
+    let fields: Vec<[String; 5]> = entries
+        .iter()
+        .map(|entry| match &entry.metadata {
+            Some(metadata) => [
+                mode(metadata),
+                metadata.nlink().to_string(),
+                user_name(metadata.uid()),
+                group_name(metadata.gid()),
+                metadata.len().to_string(),
+            ],
+            None => ["?".repeat(10), "?".into(), "?".into(), "?".into(), "?".into()],
+        })
+        .collect();
+    let widths: Vec<usize> =
+        (0..5).map(|i| fields.iter().map(|f| f[i].len()).max().unwrap_or(0)).collect();
+
+    entries
+        .iter()
+        .zip(&fields)
+        .map(|(entry, [mode, links, user, group, size])| {
+            let time = entry.metadata.as_ref().map_or("?".to_string(), |m| time(m.mtime(), now));
+            let target = match fs::read_link(&entry.path) {
+                Ok(target) if entry.metadata.as_ref().is_some_and(Metadata::is_symlink) => {
+                    format!(" -> {}", target.display())
+                }
+                _ => String::new(),
+            };
+            format!(
+                "{} {:>links_width$} {:<user_width$} {:<group_width$} {:>size_width$} {} {}{}",
+                mode,
+                links,
+                user,
+                group,
+                size,
+                time,
+                name(entry),
+                target,
+                links_width = widths[1],
+                user_width = widths[2],
+                group_width = widths[3],
+                size_width = widths[4],
+            )
+        })
+        .collect()
+}
+
+/// Type and permissions like `drwxr-xr-x`
+fn mode(metadata: &Metadata) -> String {
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else {
+        '-'
+    };
+    let mode = metadata.permissions().mode();
+    let bits = "rwxrwxrwx".chars().enumerate().map(|(i, c)| {
+        let set = mode & (1 << (8 - i)) != 0;
+        if set {
+            c
+        } else {
+            '-'
+        }
+    });
+    std::iter::once(kind).chain(bits).collect()
+}
+
+/// Local date of `seconds` since the epoch like `Mar  4 09:15`, or with the year instead
+/// of the time when it is not recent
+fn time(seconds: i64, now: i64) -> String {
+    const MONTHS: [&str; 12] =
+        ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let time = seconds as libc::time_t;
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return "?".to_string();
+    }
+    let month = MONTHS[tm.tm_mon.clamp(0, 11) as usize];
+    if (now - seconds).abs() < RECENT {
+        format!("{} {:>2} {:02}:{:02}", month, tm.tm_mday, tm.tm_hour, tm.tm_min)
+    } else {
+        format!("{} {:>2} {:>5}", month, tm.tm_mday, tm.tm_year + 1900)
+    }
+}
+
+/// Moves or copies the sources in `operands` to the last one, into it when it is a
+/// directory. `apply` is given the operand, the destination as the user would write it,
+/// the source and the destination. A file is never moved or copied onto itself
+fn transfer(
+    cmd: &str,
+    operands: &[&str],
+    cwd: &Path,
+    apply: impl Fn(&str, &str, &Path, &Path) -> Result<(), String>,
+) -> Output {
+    let (destination, sources) = match operands.split_last() {
+        Some((destination, sources)) if !sources.is_empty() => (destination, sources),
+        Some((operand, _)) => {
+            let message = format!("{}: missing destination file operand after '{}'", cmd, operand);
+            return Output::error(message);
+        }
+        None => return Output::error(format!("{}: missing file operand", cmd)),
+    };
+    let destination_path = resolve(cwd, destination);
+    let into_dir = destination_path.is_dir();
+    if sources.len() > 1 && !into_dir {
+        return Output::error(format!("{}: target '{}' is not a directory", cmd, destination));
+    }
+
+    let stderr = sources
+        .iter()
+        .filter_map(|source| {
+            let from = resolve(cwd, source);
+            let (to, target) = match from.file_name() {
+                Some(name) if into_dir => {
+                    let target = Path::new(destination).join(name).display().to_string();
+                    (destination_path.join(name), target)
+                }
+                _ => (destination_path.clone(), destination.to_string()),
+            };
+            if is_same_file(&from, &to) {
+                return Some(format!("{}: '{}' and '{}' are the same file", cmd, source, target));
+            }
+            apply(source, &target, &from, &to).err()
+        })
+        .collect();
+    Output { stdout: vec![], stderr }
+}
+
+/// Whether both paths lead to the same file, like a file and a link to it
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// Whether `path`, which need not exist yet, is `dir` or somewhere below it
+fn is_inside(path: &Path, dir: &Path) -> bool {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+    match (parent.canonicalize(), dir.canonicalize()) {
+        (Ok(parent), Ok(dir)) => parent.join(name).starts_with(dir),
+        _ => false,
+    }
+}
+
+/// Copies a file, or a directory with everything in it
+fn copy(from: &Path, to: &Path) -> io::Result<()> {
+    if !from.is_dir() {
+        return fs::copy(from, to).map(|_| ());
+    }
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+/// Splits options like `-la` from the operands, `--` ends the options. `allowed` are the
+/// option letters the command takes
+fn options<'a>(
+    cmd: &str,
+    args: &[&'a str],
+    allowed: &str,
+) -> Result<(String, Vec<&'a str>), String> {
+    let mut options = String::new();
+    let mut operands = vec![];
+    let mut args = args.iter();
+    for arg in args.by_ref() {
+        match arg.strip_prefix('-') {
+            Some("-") => break,
+            Some(letters) if !letters.is_empty() => {
+                if let Some(c) = letters.chars().find(|&c| !allowed.contains(c)) {
+                    return Err(format!("{}: invalid option -- '{}'", cmd, c));
+                }
+                options.push_str(letters);
+            }
+            _ => operands.push(*arg),
+        }
+    }
+    operands.extend(args);
+    Ok((options, operands))
+}
+
+fn resolve(cwd: &Path, arg: &str) -> PathBuf {
+    cwd.join(arg)
+}
+
+/// The error without the `(os error N)` Rust adds to it, like other shells show it
+pub fn message(error: &io::Error) -> String {
+    let text = error.to_string();
+    text.split(" (os error").next().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::testing::TempDir;
+
+    fn temp_dir(name: &str) -> TempDir {
+        let dir = TempDir::new(name);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("notes.txt"), "first\nsecond\n").unwrap();
+        fs::write(dir.join(".hidden"), "").unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        dir
+    }
+
+    fn dir(name: &str) -> String {
+        format!("{}{}{}", DIRECTORY_COLOR, name, RESET)
+    }
+
+    #[test]
+    fn ls_lists_in_columns() {
+        let cwd = temp_dir("ls");
+        assert_eq!(ls(&[], &cwd, Some(80)).stdout, [format!("notes.txt  {}", dir("src"))]);
+        assert_eq!(ls(&[], &cwd, Some(12)).stdout, ["notes.txt", &dir("src")]);
+        assert_eq!(
+            ls(&["-a"], &cwd, Some(80)).stdout,
+            [format!(
+                "{}          {}         .hidden    notes.txt  {}",
+                dir("."),
+                dir(".."),
+                dir("src")
+            )]
+        );
+        let output = ls(&["src", "notes.txt"], &cwd, Some(80));
+        assert_eq!(output.stdout, ["notes.txt", "", "src:", "main.rs"]);
+        // Piped into another command there is one plain name per line
+        assert_eq!(ls(&[], &cwd, None).stdout, ["notes.txt", "src"]);
+
+        let output = ls(&["missing", "-z"], &cwd, Some(80));
+        assert_eq!(output, Output::error("ls: invalid option -- 'z'".to_string()));
+        let output = ls(&["missing"], &cwd, Some(80));
+        assert_eq!(output.stderr, ["ls: cannot access 'missing': No such file or directory"]);
+    }
+
+    #[test]
+    fn ls_long_shows_mode_and_size() {
+        let cwd = temp_dir("ls-long");
+        fs::set_permissions(cwd.join("notes.txt"), fs::Permissions::from_mode(0o640)).unwrap();
+        let output = ls(&["-l"], &cwd, Some(80)).stdout;
+        assert!(output[0].starts_with("total "), "{:?}", output);
+        assert!(output[1].starts_with("-rw-r----- 1 "), "{:?}", output);
+        assert!(output[1].contains(" 13 "), "{:?}", output);
+        assert!(output[1].ends_with(" notes.txt"), "{:?}", output);
+        assert!(output[2].starts_with('d') && output[2].ends_with(&dir("src")), "{:?}", output);
+    }
+
+    #[test]
+    fn files_are_created_copied_moved_and_removed() {
+        let cwd = temp_dir("files");
+        let output = mkdir(&["a/b"], &cwd);
+        assert!(output.stderr[0].starts_with("mkdir: cannot create directory 'a/b': "));
+        assert_eq!(mkdir(&["-p", "a/b"], &cwd).status(), 0);
+        assert_eq!(touch(&["a/b/new"], &cwd).status(), 0);
+        let output = cat(&["notes.txt", "a/b/new", "gone"], &cwd, None);
+        assert_eq!(output.stdout, ["first", "second"]);
+        assert_eq!(output.stderr, ["cat: gone: No such file or directory"]);
+        let output = cat(&["-", "notes.txt"], &cwd, Some("piped\n"));
+        assert_eq!(output.stdout, ["piped", "first", "second"]);
+
+        let output = cp(&["src", "copy"], &cwd);
+        assert_eq!(output.stderr, ["cp: -r not specified; omitting directory 'src'"]);
+        assert_eq!(cp(&["-r", "src", "copy"], &cwd).status(), 0);
+        assert_eq!(cat(&["copy/main.rs"], &cwd, None).stdout, ["fn main() {}"]);
+        assert_eq!(cp(&["notes.txt", "copy/main.rs", "a"], &cwd).status(), 0);
+        assert!(cwd.join("a/notes.txt").is_file() && cwd.join("a/main.rs").is_file());
+        assert_eq!(
+            cp(&["notes.txt", "src/main.rs", "notes.txt"], &cwd).stderr,
+            ["cp: target 'notes.txt' is not a directory"]
+        );
+
+        assert_eq!(mv(&["notes.txt", "renamed.txt"], &cwd).status(), 0);
+        assert_eq!(mv(&["renamed.txt", "src"], &cwd).status(), 0);
+        assert!(cwd.join("src/renamed.txt").is_file() && !cwd.join("renamed.txt").exists());
+
+        assert_eq!(rm(&["a"], &cwd).stderr, ["rm: cannot remove 'a': Is a directory"]);
+        assert_eq!(rm(&["-r", "a", "copy"], &cwd).status(), 0);
+        assert!(!cwd.join("a").exists() && !cwd.join("copy").exists());
+        let output = rm(&["gone"], &cwd);
+        assert_eq!(output.stderr, ["rm: cannot remove 'gone': No such file or directory"]);
+        assert_eq!(rm(&["-f", "gone"], &cwd), Output::default());
+    }
+
+    #[test]
+    fn files_are_not_copied_onto_themselves() {
+        let cwd = temp_dir("same");
+        let output = cp(&["notes.txt", "notes.txt"], &cwd);
+        assert_eq!(output.stderr, ["cp: 'notes.txt' and 'notes.txt' are the same file"]);
+        let output = cp(&["src/main.rs", "src"], &cwd);
+        assert_eq!(output.stderr, ["cp: 'src/main.rs' and 'src/main.rs' are the same file"]);
+        let output = mv(&["notes.txt", "./notes.txt"], &cwd);
+        assert_eq!(output.stderr, ["mv: 'notes.txt' and './notes.txt' are the same file"]);
+        assert_eq!(cat(&["notes.txt", "src/main.rs"], &cwd, None).stdout.len(), 3);
+
+        let output = cp(&["-r", "src", "src/copy"], &cwd);
+        assert_eq!(output.stderr, ["cp: cannot copy a directory, 'src', into itself, 'src/copy'"]);
+        let output = cp(&["-r", "src", "src"], &cwd);
+        assert_eq!(output.stderr, ["cp: cannot copy a directory, 'src', into itself, 'src/src'"]);
+        let output = mv(&["src", "src/moved"], &cwd);
+        assert_eq!(
+            output.stderr,
+            ["mv: cannot move 'src' to a subdirectory of itself, 'src/moved'"]
+        );
+        assert_eq!(ls(&["src"], &cwd, None).stdout, ["main.rs"]);
+
+        let output = rm(&["-r", ".", "src/..", "../"], &cwd);
+        assert_eq!(
+            output.stderr,
+            [
+                "rm: refusing to remove '.' or '..' directory: skipping '.'",
+                "rm: refusing to remove '.' or '..' directory: skipping 'src/..'",
+                "rm: refusing to remove '.' or '..' directory: skipping '../'",
+            ]
+        );
+        assert!(cwd.join("src/main.rs").is_file());
+    }
+
+    #[test]
+    fn modes_are_written_like_ls() {
+        let cwd = temp_dir("mode");
+        let file = cwd.join("notes.txt");
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o754)).unwrap();
+        assert_eq!(mode(&fs::metadata(&file).unwrap()), "-rwxr-xr--");
+    }
+}
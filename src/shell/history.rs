@@ -0,0 +1,184 @@
+use std::{
+    collections::HashSet,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, Write},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+/// Commands entered at the prompt, oldest first, kept in a file shared by every terminal
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    /// `None` keeps the history in memory only
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// Entries kept, older ones are dropped from the file when it is compacted
+    const CAPACITY: usize = 1000;
+
+    /// `$XDG_DATA_HOME/terminal-emulator/history`, falling back to `~/.local/share`
+    pub fn default_path() -> Option<PathBuf> {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+        Some(data_home.join("terminal-emulator").join("history"))
+    }
+
+    /// Reads the history saved at `path`, a missing file is an empty history
+    pub fn load(path: PathBuf) -> io::Result<History> {
+        let entries = match File::open(&path) {
+            Ok(mut file) => {
+                lock(&file, libc::LOCK_SH)?;
+                let mut text = String::new();
+                file.read_to_string(&mut text)?;
+                compact(text.lines())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        Ok(History { entries, path: Some(path) })
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Rest of the newest entry that starts with `prefix` and goes on after it
+    pub fn suggest(&self, prefix: &str) -> Option<&str> {
+        if prefix.is_empty() {
+            return None;
+        }
+        self.entries
+            .iter()
+            .rev()
+            .filter_map(|entry| entry.strip_prefix(prefix))
+            .find(|rest| !rest.is_empty())
+    }
+
+    /// Adds a command unless it is blank or starts with a space, moving an earlier copy
+    /// of it to the end, and appends it to the file
+    pub fn push(&mut self, line: &str) -> io::Result<()> {
+        if line.trim().is_empty() || line.starts_with(' ') {
+            return Ok(());
+        }
+        self.entries.retain(|entry| entry != line);
+        self.entries.push(line.to_string());
+        if self.entries.len() > History::CAPACITY {
+            self.entries.remove(0);
+        }
+        match &self.path {
+            Some(path) => append(path, line),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Appends `line` to the file while holding a lock on it, so lines written by other
+/// terminals at the same time are not interleaved, and compacts the file when it holds
+/// duplicates or more than `History::CAPACITY` lines
+fn append(path: &Path, line: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+    lock(&file, libc::LOCK_EX)?;
+    writeln!(file, "{}", line)?;
+
+    let mut text = String::new();
+    file.rewind()?;
+    file.read_to_string(&mut text)?;
+    let entries = compact(text.lines());
+    if entries.len() < text.lines().count() {
+        // Rewritten in place rather than renamed over, the lock is on this file
+        file.set_len(0)?;
+        file.write_all(
+            entries.iter().map(|entry| format!("{}\n", entry)).collect::<String>().as_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Keeps the newest copy of each line and the last `History::CAPACITY` of them
+fn compact<'a>(lines: impl DoubleEndedIterator<Item = &'a str>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut entries: Vec<String> = lines
+        .rev()
+        .filter(|line| !line.trim().is_empty() && seen.insert(*line))
+        .take(History::CAPACITY)
+        .map(str::to_string)
+        .collect();
+    entries.reverse();
+    entries
+}
+
+/// Takes an advisory lock on the file, released when it is closed
+fn lock(file: &File, operation: libc::c_int) -> io::Result<()> {
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::testing::TempDir;
+
+    #[test]
+    fn commands_are_deduplicated_and_skipped_with_a_space() {
+        let mut history = History::default();
+        for line in ["ls", "echo hi", "", " secret", "ls"] {
+            history.push(line).unwrap();
+        }
+        assert_eq!(history.entries(), ["echo hi", "ls"]);
+    }
+
+    #[test]
+    fn newest_entry_is_suggested() {
+        let mut history = History::default();
+        for line in ["cargo build", "cargo test", "cat notes", "cargo"] {
+            history.push(line).unwrap();
+        }
+        assert_eq!(history.suggest("ca"), Some("rgo"));
+        assert_eq!(history.suggest("cat"), Some(" notes"));
+        assert_eq!(history.suggest("cargo"), Some(" test"));
+        assert_eq!(history.suggest("cat notes"), None);
+        assert_eq!(history.suggest(""), None);
+    }
+
+    #[test]
+    fn terminals_share_the_file() {
+        let dir = TempDir::new("shared");
+        let path = dir.join("terminal-emulator/history");
+        let mut first = History::load(path.clone()).unwrap();
+        let mut second = History::load(path.clone()).unwrap();
+        first.push("make").unwrap();
+        second.push("cargo test").unwrap();
+        first.push(" rm -rf /tmp/x").unwrap();
+        first.push("make").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "cargo test\nmake\n");
+        let history = History::load(path.clone()).unwrap();
+        assert_eq!(history.entries(), ["cargo test", "make"]);
+    }
+
+    #[test]
+    fn file_is_capped() {
+        let dir = TempDir::new("capped");
+        let path = dir.join("terminal-emulator/history");
+        let mut history = History::load(path.clone()).unwrap();
+        for i in 0..History::CAPACITY + 10 {
+            history.push(&i.to_string()).unwrap();
+        }
+        assert_eq!(history.entries().len(), History::CAPACITY);
+        assert_eq!(history.entries()[0], "10");
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), History::CAPACITY);
+        assert_eq!(text.lines().next(), Some("10"));
+    }
+}
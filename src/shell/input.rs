@@ -0,0 +1,294 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Line being edited at the prompt, the cursor counts grapheme clusters so accented
+/// letters and emoji are moved over and erased as a whole
+#[derive(Debug, Default)]
+pub struct Input {
+    text: String,
+    cursor: usize,
+    /// Line and cursor before each edit made with `edit`, newest last
+    undo: Vec<(String, usize)>,
+}
+
+impl Input {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Text before and after the cursor
+    pub fn split(&self) -> (&str, &str) {
+        self.text.split_at(self.byte_offset(self.cursor))
+    }
+
+    /// Replaces the whole line and puts the cursor at its end
+    pub fn set(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor = self.len();
+    }
+
+    /// Empties the line for the next one, edits of this one can no longer be undone
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.undo.clear();
+    }
+
+    /// Runs `edit` so that `undo` can take it back if it changed the text
+    pub fn edit<T>(&mut self, edit: impl FnOnce(&mut Input) -> T) -> T {
+        let (text, cursor) = (self.text.clone(), self.cursor);
+        let result = edit(self);
+        if self.text != text {
+            self.undo.push((text, cursor));
+        }
+        result
+    }
+
+    /// Restores the line as it was before the last edit
+    pub fn undo(&mut self) {
+        if let Some((text, cursor)) = self.undo.pop() {
+            self.text = text;
+            self.cursor = cursor;
+        }
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.insert_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Inserts `text` at the cursor as one edit, like a paste, and moves the cursor after it
+    pub fn insert_str(&mut self, text: &str) {
+        let offset = self.byte_offset(self.cursor);
+        self.text.insert_str(offset, text);
+        // A combining mark joins the grapheme before the cursor instead of adding one
+        self.cursor = self.text[..offset + text.len()].graphemes(true).count();
+    }
+
+    /// Erases the grapheme before the cursor
+    pub fn backspace(&mut self) {
+        if self.cursor == 0 {
+            return;
+        }
+        let start = self.byte_offset(self.cursor - 1);
+        let end = self.byte_offset(self.cursor);
+        self.text.replace_range(start..end, "");
+        self.cursor -= 1;
+    }
+
+    /// Erases `count` graphemes before the cursor
+    pub fn erase_back(&mut self, count: usize) {
+        self.remove(self.cursor.saturating_sub(count), self.cursor);
+    }
+
+    /// Erases the grapheme after the cursor
+    pub fn delete(&mut self) {
+        self.remove(self.cursor, self.cursor + 1);
+    }
+
+    /// Moves the cursor by `shift` graphemes, staying inside the line
+    pub fn shift_cursor(&mut self, shift: isize) {
+        self.cursor = self.cursor.saturating_add_signed(shift).min(self.len());
+    }
+
+    pub fn move_to_start(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_to_end(&mut self) {
+        self.cursor = self.len();
+    }
+
+    /// Moves back to the start of the word before the cursor, words are letters and digits
+    pub fn word_back(&mut self) {
+        let graphemes: Vec<&str> = self.text.graphemes(true).collect();
+        let mut cursor = self.cursor;
+        while cursor > 0 && !is_alphanumeric(graphemes[cursor - 1]) {
+            cursor -= 1;
+        }
+        while cursor > 0 && is_alphanumeric(graphemes[cursor - 1]) {
+            cursor -= 1;
+        }
+        self.cursor = cursor;
+    }
+
+    /// Moves on to the end of the word after the cursor
+    pub fn word_forward(&mut self) {
+        let graphemes: Vec<&str> = self.text.graphemes(true).collect();
+        let mut cursor = self.cursor;
+        while cursor < graphemes.len() && !is_alphanumeric(graphemes[cursor]) {
+            cursor += 1;
+        }
+        while cursor < graphemes.len() && is_alphanumeric(graphemes[cursor]) {
+            cursor += 1;
+        }
+        self.cursor = cursor;
+    }
+
+    /// Removes the text from the cursor to the end of the line and returns it
+    pub fn kill_to_end(&mut self) -> String {
+        self.remove(self.cursor, self.len())
+    }
+
+    /// Removes the text from the start of the line to the cursor and returns it
+    pub fn kill_to_start(&mut self) -> String {
+        self.remove(0, self.cursor)
+    }
+
+    /// Removes the whitespace separated word before the cursor and returns it
+    pub fn kill_word_back(&mut self) -> String {
+        let graphemes: Vec<&str> = self.text.graphemes(true).collect();
+        let is_space = |grapheme: &str| grapheme.chars().all(char::is_whitespace);
+        let mut start = self.cursor;
+        while start > 0 && is_space(graphemes[start - 1]) {
+            start -= 1;
+        }
+        while start > 0 && !is_space(graphemes[start - 1]) {
+            start -= 1;
+        }
+        self.remove(start, self.cursor)
+    }
+
+    /// Removes the graphemes from `start` to `end` and puts the cursor where they were
+    fn remove(&mut self, start: usize, end: usize) -> String {
+        let end = end.min(self.len());
+        if start >= end {
+            return String::new();
+        }
+        let range = self.byte_offset(start)..self.byte_offset(end);
+        let removed = self.text[range.clone()].to_string();
+        self.text.replace_range(range, "");
+        self.cursor = start;
+        removed
+    }
+
+    /// Number of grapheme clusters
+    fn len(&self) -> usize {
+        self.text.graphemes(true).count()
+    }
+
+    fn byte_offset(&self, grapheme: usize) -> usize {
+        self.text.grapheme_indices(true).nth(grapheme).map_or(self.text.len(), |(i, _)| i)
+    }
+}
+
+fn is_alphanumeric(grapheme: &str) -> bool {
+    grapheme.chars().next().is_some_and(char::is_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str) -> Input {
+        let mut input = Input::default();
+        text.chars().for_each(|c| input.insert(c));
+        input
+    }
+
+    #[test]
+    fn ascii_editing() {
+        let mut input = typed("helo");
+        input.shift_cursor(-1);
+        input.insert('l');
+        assert_eq!(input.text(), "hello");
+        assert_eq!(input.split(), ("hell", "o"));
+        input.backspace();
+        assert_eq!(input.text(), "helo");
+        assert_eq!(input.cursor, 3);
+    }
+
+    #[test]
+    fn accented_letters() {
+        let mut input = typed("café");
+        assert_eq!(input.cursor, 4);
+        input.shift_cursor(-1);
+        input.insert('ï');
+        assert_eq!(input.text(), "cafïé");
+        input.move_to_end();
+        input.backspace();
+        input.backspace();
+        assert_eq!(input.text(), "caf");
+    }
+
+    #[test]
+    fn combining_marks_extend_the_previous_grapheme() {
+        let mut input = typed("e\u{301}a");
+        assert_eq!(input.cursor, 2);
+        input.shift_cursor(-1);
+        assert_eq!(input.split(), ("e\u{301}", "a"));
+        input.insert('\u{323}');
+        assert_eq!(input.text(), "e\u{301}\u{323}a");
+        assert_eq!(input.cursor, 1);
+        input.backspace();
+        assert_eq!(input.text(), "a");
+    }
+
+    #[test]
+    fn insert_str_puts_the_cursor_after_the_text() {
+        let mut input = typed("cd ");
+        input.shift_cursor(-1);
+        input.insert_str("/tmp/e\u{301}");
+        assert_eq!(input.text(), "cd/tmp/e\u{301} ");
+        assert_eq!(input.split(), ("cd/tmp/e\u{301}", " "));
+    }
+
+    #[test]
+    fn readline_motion_and_kills() {
+        let mut input = typed("git commit -m 'café au lait'");
+        input.word_back();
+        assert_eq!(input.split(), ("git commit -m 'café au ", "lait'"));
+        input.word_back();
+        input.word_back();
+        assert_eq!(input.split(), ("git commit -m '", "café au lait'"));
+        input.word_forward();
+        assert_eq!(input.split(), ("git commit -m 'café", " au lait'"));
+
+        assert_eq!(input.kill_word_back(), "'café");
+        assert_eq!(input.text(), "git commit -m  au lait'");
+        assert_eq!(input.kill_to_end(), " au lait'");
+        input.move_to_start();
+        input.delete();
+        assert_eq!(input.text(), "it commit -m ");
+        input.shift_cursor(3);
+        assert_eq!(input.kill_to_start(), "it ");
+        assert_eq!(input.split(), ("", "commit -m "));
+        input.move_to_end();
+        assert_eq!(input.kill_word_back(), "-m ");
+        input.delete();
+        assert_eq!(input.text(), "commit ");
+    }
+
+    #[test]
+    fn undo_takes_back_edits() {
+        let mut input = Input::default();
+        "ab".chars().for_each(|c| input.edit(|input| input.insert(c)));
+        input.edit(Input::move_to_start);
+        input.edit(Input::delete);
+        assert_eq!(input.text(), "b");
+
+        input.undo();
+        assert_eq!(input.split(), ("", "ab"));
+        input.undo();
+        assert_eq!(input.split(), ("a", ""));
+        input.undo();
+        input.undo();
+        assert_eq!(input.text(), "");
+
+        input.edit(|input| input.insert_str("x"));
+        input.clear();
+        input.undo();
+        assert_eq!(input.text(), "");
+    }
+
+    #[test]
+    fn emoji() {
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        let mut input = typed(&format!("x{family}😀y"));
+        assert_eq!(input.cursor, 4);
+        input.shift_cursor(-2);
+        assert_eq!(input.split(), (format!("x{family}").as_str(), "😀y"));
+        input.backspace();
+        assert_eq!(input.text(), "x😀y");
+        input.shift_cursor(10);
+        assert_eq!(input.cursor, 3);
+    }
+}
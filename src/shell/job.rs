@@ -0,0 +1,400 @@
+use std::{
+    env,
+    fs::{File, OpenOptions},
+    io::{self, PipeReader, Read, Write},
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::{fs::PermissionsExt, process::ExitStatusExt},
+    },
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread::{self, JoinHandle},
+};
+
+use thiserror::Error;
+
+use super::{
+    builtins,
+    directories::Directories,
+    files,
+    parser::{Redirection, Target},
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{path}: {}", files::message(.inner))]
+    Redirect {
+        path: String,
+        #[source]
+        inner: io::Error,
+    },
+    #[error("{0}: Bad file descriptor")]
+    BadFd(u32),
+    #[error("{name}: {}", files::message(.inner))]
+    Spawn {
+        name: String,
+        #[source]
+        inner: io::Error,
+    },
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+/// What a stage of a pipeline runs
+pub enum Program {
+    Builtin(String),
+    External(PathBuf),
+}
+
+/// A command of a pipeline ready to start
+pub struct Stage {
+    pub program: Program,
+    pub args: Vec<String>,
+    pub redirections: Vec<Redirection>,
+}
+
+/// Standard input, output and error of a command, `None` is the terminal or, for input,
+/// nothing
+#[derive(Debug, Default)]
+pub struct Streams {
+    pub stdin: Option<OwnedFd>,
+    pub stdout: Option<OwnedFd>,
+    pub stderr: Option<OwnedFd>,
+}
+
+impl Streams {
+    /// Applies `redirections` from the first, files are relative to `cwd`
+    pub fn redirect(mut self, redirections: &[Redirection], cwd: &Path) -> Result<Streams, Error> {
+        for Redirection { fd, target } in redirections {
+            let mut options = OpenOptions::new();
+            let stream = match target {
+                Target::Read(path) => Some(open(cwd, path, options.read(true))?),
+                Target::Write(path) => {
+                    Some(open(cwd, path, options.write(true).create(true).truncate(true))?)
+                }
+                Target::Append(path) => Some(open(cwd, path, options.append(true).create(true))?),
+                Target::Fd(from) => {
+                    self.get_mut(*from)?.as_ref().map(OwnedFd::try_clone).transpose()?
+                }
+            };
+            *self.get_mut(*fd)? = stream;
+        }
+        Ok(self)
+    }
+
+    fn get_mut(&mut self, fd: u32) -> Result<&mut Option<OwnedFd>, Error> {
+        match fd {
+            0 => Ok(&mut self.stdin),
+            1 => Ok(&mut self.stdout),
+            2 => Ok(&mut self.stderr),
+            fd => Err(Error::BadFd(fd)),
+        }
+    }
+}
+
+fn open(cwd: &Path, path: &str, options: &OpenOptions) -> Result<OwnedFd, Error> {
+    let file = options.open(cwd.join(path));
+    file.map(OwnedFd::from).map_err(|inner| Error::Redirect { path: path.to_string(), inner })
+}
+
+/// A started stage of a pipeline
+enum Process {
+    External(Child),
+    Builtin { thread: Option<JoinHandle<i32>>, status: Option<i32> },
+}
+
+impl Process {
+    /// Exit status once it ended
+    fn status(&mut self) -> Option<i32> {
+        match self {
+            Process::External(child) => child.try_wait().ok().flatten().map(exit_code),
+            Process::Builtin { thread, status } => {
+                if thread.as_ref().is_some_and(JoinHandle::is_finished) {
+                    let thread = thread.take()?;
+                    *status = Some(thread.join().unwrap_or(1));
+                }
+                *status
+            }
+        }
+    }
+}
+
+/// Pipeline started by the shell. Built-ins in it run on threads, programs as child
+/// processes, what they all write to the terminal is collected with [`Job::read`].
+/// Keys are not forwarded, the first stage reads from `/dev/null` unless its input is
+/// redirected
+pub struct Job {
+    processes: Vec<Process>,
+    /// Read end of the pipe the stages write to, never blocks
+    output: PipeReader,
+    /// All stages exited and what they wrote was read
+    done: bool,
+}
+
+impl Job {
+    /// Starts the stages in `dirs` with the environment of the shell, each reading what
+    /// the one before wrote. Built-ins get a copy of `dirs`, a `cd` in a pipeline does
+    /// not change the directory of the shell
+    pub fn spawn(stages: Vec<Stage>, dirs: &Directories) -> Result<Job, Error> {
+        let (output, terminal) = io::pipe()?;
+        set_nonblocking(&output)?;
+        let terminal = OwnedFd::from(terminal);
+        // Dropped when a stage fails to start, which stops the ones started before it
+        let mut job = Job { processes: vec![], output, done: false };
+        let mut stdin = None;
+        let count = stages.len();
+        for (i, stage) in stages.into_iter().enumerate() {
+            let (next, stdout) = if i + 1 < count {
+                let (next, stdout) = io::pipe()?;
+                (Some(next.into()), stdout.into())
+            } else {
+                (None, terminal.try_clone()?)
+            };
+            let streams = Streams {
+                stdin: stdin.take(),
+                stdout: Some(stdout),
+                stderr: Some(terminal.try_clone()?),
+            };
+            stdin = next;
+            let streams = streams.redirect(&stage.redirections, dirs.cwd())?;
+            job.processes.push(start(stage, streams, dirs)?);
+        }
+        // Only the stages hold the write end now
+        drop(terminal);
+        Ok(job)
+    }
+
+    /// Collects everything written since the last call. Returns `None` once all stages
+    /// exited, even when a program they started in the background still has the output
+    /// open
+    pub fn read(&mut self) -> Option<Vec<u8>> {
+        if self.done {
+            return None;
+        }
+        // Checked first, what the stages wrote before exiting is then waiting in the pipe
+        let exited = self.processes.iter_mut().all(|process| process.status().is_some());
+        let mut data = vec![];
+        let mut buf = [0; 4096];
+        loop {
+            match self.output.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                // Nothing more for now
+                Err(_) => break,
+            }
+        }
+        self.done = exited;
+        Some(data)
+    }
+
+    /// Exit status of the last stage, like other shells give for a pipeline
+    pub fn status(&mut self) -> Option<i32> {
+        self.processes.last_mut()?.status()
+    }
+
+    /// Sends SIGINT to the programs like Ctrl+C in a terminal
+    pub fn interrupt(&self) {
+        for process in &self.processes {
+            if let Process::External(child) = process {
+                unsafe {
+                    libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        for process in &mut self.processes {
+            if let Process::External(child) = process {
+                if let Ok(None) = child.try_wait() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+            }
+        }
+    }
+}
+
+fn start(stage: Stage, streams: Streams, dirs: &Directories) -> Result<Process, Error> {
+    let Streams { stdin, stdout, stderr } = streams;
+    match stage.program {
+        Program::External(program) => {
+            let child = Command::new(&program)
+                .args(&stage.args)
+                .current_dir(dirs.cwd())
+                .env("PWD", dirs.cwd())
+                .stdin(stdin.map_or_else(Stdio::null, Stdio::from))
+                .stdout(stdout.map_or_else(Stdio::inherit, Stdio::from))
+                .stderr(stderr.map_or_else(Stdio::inherit, Stdio::from))
+                .spawn()
+                .map_err(|inner| Error::Spawn { name: program.display().to_string(), inner })?;
+            Ok(Process::External(child))
+        }
+        Program::Builtin(name) => {
+            let mut dirs = dirs.clone();
+            let thread = thread::spawn(move || {
+                let input = stdin.map(read_input);
+                let args: Vec<&str> = stage.args.iter().map(String::as_str).collect();
+                let output = builtins::run(&name, &args, &mut dirs, None, input.as_deref());
+                if let Some(stdout) = stdout {
+                    let _ = write_lines(stdout, &output.stdout);
+                }
+                if let Some(stderr) = stderr {
+                    let _ = write_lines(stderr, &output.stderr);
+                }
+                output.status()
+            });
+            Ok(Process::Builtin { thread: Some(thread), status: None })
+        }
+    }
+}
+
+/// Everything written to `fd` until it is closed, invalid UTF-8 is replaced
+pub fn read_input(fd: OwnedFd) -> String {
+    let mut input = vec![];
+    let _ = File::from(fd).read_to_end(&mut input);
+    String::from_utf8_lossy(&input).into_owned()
+}
+
+/// Writes each line followed by a newline to `fd`
+pub fn write_lines(fd: OwnedFd, lines: &[String]) -> io::Result<()> {
+    let mut file = File::from(fd);
+    for line in lines {
+        writeln!(file, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// Status like `$?`, 128 and the signal for a program killed by one
+fn exit_code(status: ExitStatus) -> i32 {
+    status.code().unwrap_or_else(|| 128 + status.signal().unwrap_or(0))
+}
+
+/// Executable for `name`: a path relative to `cwd` if it has a `/`, otherwise the first
+/// match in the directories of `$PATH`
+pub fn find_program(name: &str, cwd: &Path) -> Option<PathBuf> {
+    if name.contains('/') {
+        return Some(cwd.join(name)).filter(|path| is_executable(path));
+    }
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path).map(|dir| dir.join(name)).find(|path| is_executable(path))
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+fn set_nonblocking(fd: &impl AsRawFd) -> io::Result<()> {
+    let fd = fd.as_raw_fd();
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::testing::TempDir;
+
+    #[test]
+    fn programs_are_found_on_path() {
+        let sh = find_program("sh", Path::new("/")).unwrap();
+        assert!(sh.ends_with("sh"));
+        assert_eq!(find_program("no-such-program-here", Path::new("/")), None);
+        assert_eq!(find_program("./bin/sh", Path::new("/")), Some(PathBuf::from("/./bin/sh")));
+        // Directories are not programs
+        assert_eq!(find_program("./bin", Path::new("/")), None);
+    }
+
+    fn stage(program: &str, args: &[&str], redirections: Vec<Redirection>) -> Stage {
+        let program = match find_program(program, Path::new("/")) {
+            _ if builtins::NAMES.contains(&program) => Program::Builtin(program.to_string()),
+            Some(path) => Program::External(path),
+            None => panic!("{} not found", program),
+        };
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        Stage { program, args, redirections }
+    }
+
+    fn output(mut job: Job) -> (String, Option<i32>) {
+        let mut output = vec![];
+        while let Some(data) = job.read() {
+            output.extend(data);
+            thread::yield_now();
+        }
+        (String::from_utf8(output).unwrap(), job.status())
+    }
+
+    #[test]
+    fn output_is_read_until_exit() {
+        let stages = vec![stage("sh", &["-c", "pwd; echo oops >&2; exit 3"], vec![])];
+        let job = Job::spawn(stages, &Directories::new(PathBuf::from("/"))).unwrap();
+        let (output, status) = output(job);
+        let mut lines: Vec<&str> = output.lines().collect();
+        lines.sort();
+        assert_eq!(lines, ["/", "oops"]);
+        assert_eq!(status, Some(3));
+    }
+
+    #[test]
+    fn job_ends_when_its_stages_exit() {
+        // The background sleep keeps the output open after the shell exited
+        let stages = vec![stage("sh", &["-c", "sleep 5 & echo started"], vec![])];
+        let started = std::time::Instant::now();
+        let job = Job::spawn(stages, &Directories::new(PathBuf::from("/"))).unwrap();
+        assert_eq!(output(job), ("started\n".into(), Some(0)));
+        assert!(started.elapsed() < std::time::Duration::from_secs(4));
+    }
+
+    #[test]
+    fn interrupt_stops_the_programs() {
+        let stages = vec![stage("sh", &["-c", "while :; do :; done"], vec![])];
+        let job = Job::spawn(stages, &Directories::new(PathBuf::from("/"))).unwrap();
+        job.interrupt();
+        assert_eq!(output(job), ("".into(), Some(128 + libc::SIGINT)));
+    }
+
+    #[test]
+    fn stages_are_piped_and_redirected() {
+        let dir = TempDir::new("job");
+        let dirs = Directories::new(dir.to_path_buf());
+
+        let stages = vec![
+            stage("sh", &["-c", "echo b; echo a"], vec![]),
+            stage("sh", &["-c", "read x; read y; echo \"$y $x\""], vec![]),
+        ];
+        assert_eq!(output(Job::spawn(stages, &dirs).unwrap()), ("a b\n".into(), Some(0)));
+        // Built-ins are piped into programs too
+        let stages = vec![
+            stage("echo", &["hi"], vec![]),
+            stage("sh", &["-c", "read x; echo \"<$x>\""], vec![]),
+        ];
+        assert_eq!(output(Job::spawn(stages, &dirs).unwrap()), ("<hi>\n".into(), Some(0)));
+
+        // Both streams go to the file, nothing to the terminal
+        let redirections = vec![
+            Redirection { fd: 1, target: Target::Write("out".into()) },
+            Redirection { fd: 2, target: Target::Fd(1) },
+        ];
+        let stages = vec![stage("sh", &["-c", "echo out; echo err >&2"], redirections)];
+        assert_eq!(output(Job::spawn(stages, &dirs).unwrap()), ("".into(), Some(0)));
+        let stages = vec![stage(
+            "cat",
+            &[],
+            vec![Redirection { fd: 0, target: Target::Read("out".into()) }],
+        )];
+        assert_eq!(output(Job::spawn(stages, &dirs).unwrap()), ("out\nerr\n".into(), Some(0)));
+
+        let redirections = vec![Redirection { fd: 0, target: Target::Read("missing".into()) }];
+        let error = Job::spawn(vec![stage("cat", &[], redirections)], &dirs).err().unwrap();
+        assert_eq!(error.to_string(), "missing: No such file or directory");
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::VecDeque;
+
+/// Text removed by kill commands, newest first, for yanking back into the input
+#[derive(Debug, Default)]
+pub struct KillRing {
+    kills: VecDeque<String>,
+    /// Entry yanked last, Alt+Y moves on to the older ones
+    yanked: usize,
+}
+
+impl KillRing {
+    /// Entries kept before the oldest is dropped
+    const CAPACITY: usize = 16;
+
+    /// Adds killed text, a kill right after another one joins its entry. `backward`
+    /// kills are put in front of it so the text stays in the order it was in the line
+    pub fn kill(&mut self, text: String, backward: bool, join: bool) {
+        if text.is_empty() {
+            return;
+        }
+        match self.kills.front_mut() {
+            Some(last) if join && backward => last.insert_str(0, &text),
+            Some(last) if join => last.push_str(&text),
+            _ => {
+                self.kills.push_front(text);
+                self.kills.truncate(KillRing::CAPACITY);
+            }
+        }
+    }
+
+    /// The newest entry
+    pub fn yank(&mut self) -> Option<&str> {
+        self.yanked = 0;
+        self.kills.front().map(String::as_str)
+    }
+
+    /// The entry before the one yanked last, going round to the newest after the oldest
+    pub fn yank_pop(&mut self) -> Option<&str> {
+        if self.kills.is_empty() {
+            return None;
+        }
+        self.yanked = (self.yanked + 1) % self.kills.len();
+        self.kills.get(self.yanked).map(String::as_str)
+    }
+}
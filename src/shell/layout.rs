@@ -0,0 +1,74 @@
+use unicode_width::UnicodeWidthStr;
+
+/// Columns between two items of a listing
+const GAP: usize = 2;
+
+/// Lays out `items` in as many columns as fit in `width` cells, going down each column
+/// first like `ls`. Items can be colored with SGR sequences. Every line ends with a newline
+pub fn columns(items: &[String], width: usize) -> String {
+    if items.is_empty() {
+        return String::new();
+    }
+    let widest = items.iter().map(|item| cells(item)).max().unwrap_or(0) + GAP;
+    let cols = (width / widest).clamp(1, items.len());
+    let rows = items.len().div_ceil(cols);
+
+    let mut text = String::new();
+    for row in 0..rows {
+        let line: Vec<&String> = items.iter().skip(row).step_by(rows).collect();
+        for (i, item) in line.iter().enumerate() {
+            text.push_str(item);
+            if i + 1 < line.len() {
+                text.push_str(&" ".repeat(widest - cells(item)));
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Cells `text` takes on the screen, leaving out the escape sequences in it
+fn cells(text: &str) -> usize {
+    let mut visible = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // A CSI sequence ends with a letter or another final byte
+            chars.by_ref().skip(1).find(|c| ('\x40'..='\x7e').contains(c));
+        } else {
+            visible.push(c);
+        }
+    }
+    visible.width()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn items_go_down_the_columns() {
+        let items = items(&["a", "bb", "c", "dddd", "e"]);
+        assert_eq!(columns(&items, 18), "a     c     e\nbb    dddd\n");
+        assert_eq!(columns(&items, 80), "a     bb    c     dddd  e\n");
+        // An item wider than the line still gets one of its own
+        assert_eq!(columns(&items, 3), "a\nbb\nc\ndddd\ne\n");
+        assert_eq!(columns(&[], 80), "");
+    }
+
+    #[test]
+    fn wide_characters_take_two_cells() {
+        let items = items(&["日本", "ab", "c"]);
+        assert_eq!(columns(&items, 13), "日本  c\nab\n");
+    }
+
+    #[test]
+    fn colors_take_no_cells() {
+        let items = items(&["\x1b[1;34mdir\x1b[0m", "file"]);
+        assert_eq!(columns(&items, 12), "\x1b[1;34mdir\x1b[0m   file\n");
+    }
+}
@@ -0,0 +1,413 @@
+use std::{iter::Peekable, str::Chars};
+
+use thiserror::Error;
+
+use super::directories::home;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Error {
+    #[error("unexpected end of line while looking for matching `{0}'")]
+    UnterminatedQuote(char),
+    #[error("syntax error near unexpected token `{0}'")]
+    Unexpected(String),
+    #[error("syntax error: unexpected end of line")]
+    UnexpectedEnd,
+}
+
+/// A program or built-in with its arguments, quotes are already removed from the words
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Command {
+    pub words: Vec<String>,
+    /// In the order they were written, later ones win
+    pub redirections: Vec<Redirection>,
+}
+
+/// `fd` of a command opened on a file or made a copy of another descriptor
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Redirection {
+    pub fd: u32,
+    pub target: Target,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// `< file`
+    Read(String),
+    /// `> file`
+    Write(String),
+    /// `>> file`
+    Append(String),
+    /// `>&fd`, like `2>&1`
+    Fd(u32),
+}
+
+/// Commands joined by `|`, each reading what the one before wrote
+pub type Pipeline = Vec<Command>;
+
+/// How a pipeline of a list depends on the one before it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Connector {
+    /// First of the list or after `;`, runs anyway
+    Then,
+    /// After `&&`, runs if the one before succeeded
+    And,
+    /// After `||`, runs if the one before failed
+    Or,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Pipe,
+    And,
+    Or,
+    Semicolon,
+    /// `<`, `>`, `>>` or `>&` with the descriptor written before it
+    Redirect(Option<u32>, &'static str),
+}
+
+impl Token {
+    fn text(&self) -> String {
+        match self {
+            Token::Word(word) => word.clone(),
+            Token::Pipe => "|".into(),
+            Token::And => "&&".into(),
+            Token::Or => "||".into(),
+            Token::Semicolon => ";".into(),
+            Token::Redirect(_, op) => op.to_string(),
+        }
+    }
+}
+
+/// Parses a line into a list of pipelines joined by `;`, `&&` and `||`
+pub fn parse(line: &str) -> Result<Vec<(Connector, Pipeline)>, Error> {
+    let mut tokens = lex(line)?.into_iter().peekable();
+    let mut list = vec![];
+    let mut connector = Connector::Then;
+    while tokens.peek().is_some() {
+        let mut pipeline = vec![parse_command(&mut tokens)?];
+        while tokens.next_if_eq(&Token::Pipe).is_some() {
+            pipeline.push(parse_command(&mut tokens)?);
+        }
+        list.push((connector, pipeline));
+
+        connector = match tokens.next() {
+            None => break,
+            Some(Token::Semicolon) => Connector::Then,
+            Some(Token::And) => Connector::And,
+            Some(Token::Or) => Connector::Or,
+            Some(token) => return Err(Error::Unexpected(token.text())),
+        };
+        if tokens.peek().is_none() && connector != Connector::Then {
+            return Err(Error::UnexpectedEnd);
+        }
+    }
+    Ok(list)
+}
+
+fn parse_command(tokens: &mut Peekable<impl Iterator<Item = Token>>) -> Result<Command, Error> {
+    let mut command = Command::default();
+    loop {
+        match tokens.peek() {
+            Some(Token::Word(_)) => {
+                if let Some(Token::Word(word)) = tokens.next() {
+                    command.words.push(word);
+                }
+            }
+            Some(&Token::Redirect(fd, op)) => {
+                tokens.next();
+                let word = match tokens.next() {
+                    Some(Token::Word(word)) => word,
+                    Some(token) => return Err(Error::Unexpected(token.text())),
+                    None => return Err(Error::UnexpectedEnd),
+                };
+                let (fd, target) = match op {
+                    "<" => (fd.unwrap_or(0), Target::Read(word)),
+                    ">" => (fd.unwrap_or(1), Target::Write(word)),
+                    ">>" => (fd.unwrap_or(1), Target::Append(word)),
+                    _ => {
+                        let target = word.parse().map_err(|_| Error::Unexpected(word))?;
+                        (fd.unwrap_or(1), Target::Fd(target))
+                    }
+                };
+                command.redirections.push(Redirection { fd, target });
+            }
+            Some(token) if command.words.is_empty() && command.redirections.is_empty() => {
+                return Err(Error::Unexpected(token.text()))
+            }
+            None if command.words.is_empty() && command.redirections.is_empty() => {
+                return Err(Error::UnexpectedEnd)
+            }
+            _ => return Ok(command),
+        }
+    }
+}
+
+/// Splits the line into words and operators. Quotes and backslashes are removed from
+/// words and a `~` starting one is the home directory
+fn lex(line: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    // The word being read, `None` between words so `""` still makes an empty one
+    let mut word: Option<String> = None;
+    // Whether the word has only digits written without quotes, a descriptor for `2>`
+    let mut plain_digits = true;
+
+    while let Some(c) = chars.next() {
+        let operator = match c {
+            c if c.is_whitespace() => None,
+            '|' if chars.next_if_eq(&'|').is_some() => Some(Token::Or),
+            '|' => Some(Token::Pipe),
+            '&' if chars.next_if_eq(&'&').is_some() => Some(Token::And),
+            '&' => return Err(Error::Unexpected("&".into())),
+            ';' => Some(Token::Semicolon),
+            '<' | '>' => {
+                let op = match c {
+                    '<' => "<",
+                    _ if chars.next_if_eq(&'>').is_some() => ">>",
+                    _ if chars.next_if_eq(&'&').is_some() => ">&",
+                    _ => ">",
+                };
+                let fd = match &word {
+                    Some(digits) if plain_digits => digits.parse().ok(),
+                    _ => None,
+                };
+                if fd.is_some() {
+                    word = None;
+                }
+                Some(Token::Redirect(fd, op))
+            }
+            _ => {
+                let word = word.get_or_insert_with(String::new);
+                match c {
+                    '~' if word.is_empty() && ends_word(chars.peek()) => match home() {
+                        Some(home) => word.push_str(&home.to_string_lossy()),
+                        None => word.push('~'),
+                    },
+                    '\'' => read_single_quoted(&mut chars, word)?,
+                    '"' => read_double_quoted(&mut chars, word)?,
+                    // A backslash at the end of the line stays as it is
+                    '\\' => word.push(chars.next().unwrap_or('\\')),
+                    c => word.push(c),
+                }
+                plain_digits &= c.is_ascii_digit();
+                continue;
+            }
+        };
+        if let Some(word) = word.take() {
+            tokens.push(Token::Word(word));
+        }
+        plain_digits = true;
+        tokens.extend(operator);
+    }
+    tokens.extend(word.map(Token::Word));
+    Ok(tokens)
+}
+
+/// Byte offset where the last word of a line being typed starts and the word as `parse`
+/// would read it, an open quote lasts to the end of the line and `~` is kept as it is
+pub fn last_word(line: &str) -> (usize, String) {
+    let mut chars = line.chars().peekable();
+    let mut start = 0;
+    let mut word = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() || "|&;<>".contains(c) => {
+                start = line.len() - chars.clone().map(char::len_utf8).sum::<usize>();
+                word.clear();
+            }
+            '\'' => {
+                let _ = read_single_quoted(&mut chars, &mut word);
+            }
+            '"' => {
+                let _ = read_double_quoted(&mut chars, &mut word);
+            }
+            '\\' => word.push(chars.next().unwrap_or('\\')),
+            c => word.push(c),
+        }
+    }
+    (start, word)
+}
+
+/// `word` with a backslash before each character `parse` would split it at or remove
+pub fn escape(word: &str) -> String {
+    let mut escaped = String::new();
+    for c in word.chars() {
+        if c.is_whitespace() || "'\"\\|&;<>$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Whether the character after `~` ends the home directory part of a word
+fn ends_word(next: Option<&char>) -> bool {
+    match next {
+        None | Some('/') => true,
+        Some(&c) => c.is_whitespace() || "|&;<>".contains(c),
+    }
+}
+
+/// Everything up to the closing quote as it is
+fn read_single_quoted(chars: &mut Peekable<Chars>, word: &mut String) -> Result<(), Error> {
+    for c in chars.by_ref() {
+        if c == '\'' {
+            return Ok(());
+        }
+        word.push(c);
+    }
+    Err(Error::UnterminatedQuote('\''))
+}
+
+/// Up to the closing quote, a backslash only escapes `"`, `\`, `$` and `` ` ``
+fn read_double_quoted(chars: &mut Peekable<Chars>, word: &mut String) -> Result<(), Error> {
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Ok(()),
+            '\\' => match chars.next_if(|c| "\"\\$`".contains(*c)) {
+                Some(escaped) => word.push(escaped),
+                None => word.push('\\'),
+            },
+            c => word.push(c),
+        }
+    }
+    Err(Error::UnterminatedQuote('"'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        let list = parse(line).unwrap();
+        assert_eq!(list.len(), 1, "{:?}", list);
+        assert_eq!(list[0].1.len(), 1, "{:?}", list);
+        list[0].1[0].words.clone()
+    }
+
+    fn command(words: &[&str]) -> Command {
+        Command { words: words.iter().map(|w| w.to_string()).collect(), redirections: vec![] }
+    }
+
+    fn redirected(words: &[&str], redirections: &[(u32, Target)]) -> Command {
+        let redirections = redirections
+            .iter()
+            .map(|(fd, target)| Redirection { fd: *fd, target: target.clone() })
+            .collect();
+        Command { redirections, ..command(words) }
+    }
+
+    #[test]
+    fn words_are_split_on_whitespace() {
+        assert_eq!(words("  ls\t-l   src "), ["ls", "-l", "src"]);
+        assert_eq!(parse("").unwrap(), []);
+        assert_eq!(parse("   ").unwrap(), []);
+    }
+
+    #[test]
+    fn quotes_keep_spaces_and_operators() {
+        assert_eq!(words(r#"echo "a  b" 'c | d'"#), ["echo", "a  b", "c | d"]);
+        assert_eq!(words(r#"echo "it's" 'say "hi"'"#), ["echo", "it's", "say \"hi\""]);
+        // Quoted parts join the word around them and empty quotes are a word
+        assert_eq!(words(r#"echo pre"mid"'post' "" ''"#), ["echo", "premidpost", "", ""]);
+        assert_eq!(words(r#"echo '$HOME \n'"#), ["echo", "$HOME \\n"]);
+    }
+
+    #[test]
+    fn backslashes_escape() {
+        assert_eq!(words(r"echo a\ b \| \\ \'"), ["echo", "a b", "|", "\\", "'"]);
+        assert_eq!(words(r#"echo "a\"b\\c\d""#), ["echo", "a\"b\\c\\d"]);
+        assert_eq!(words(r"echo end\"), ["echo", "end\\"]);
+    }
+
+    #[test]
+    fn tilde_starts_a_word_with_home() {
+        let Some(home) = home() else {
+            return;
+        };
+        let home = home.to_string_lossy();
+        assert_eq!(words("cd ~"), ["cd", &home]);
+        assert_eq!(
+            words("ls ~/src a~ ~user '~'"),
+            ["ls".to_string(), format!("{}/src", home), "a~".into(), "~user".into(), "~".into()]
+        );
+    }
+
+    #[test]
+    fn pipelines_and_lists() {
+        let list = parse("ls | sort -r|head; make && echo ok || echo failed;").unwrap();
+        assert_eq!(
+            list,
+            [
+                (
+                    Connector::Then,
+                    vec![command(&["ls"]), command(&["sort", "-r"]), command(&["head"])]
+                ),
+                (Connector::Then, vec![command(&["make"])]),
+                (Connector::And, vec![command(&["echo", "ok"])]),
+                (Connector::Or, vec![command(&["echo", "failed"])]),
+            ]
+        );
+    }
+
+    #[test]
+    fn redirections() {
+        let list = parse("sort < in.txt > out.txt 2>> errors.log").unwrap();
+        assert_eq!(
+            list[0].1,
+            [redirected(
+                &["sort"],
+                &[
+                    (0, Target::Read("in.txt".into())),
+                    (1, Target::Write("out.txt".into())),
+                    (2, Target::Append("errors.log".into())),
+                ]
+            )]
+        );
+
+        let list = parse("make 2>&1 | less; echo oops >&2; echo >>log x").unwrap();
+        assert_eq!(list[0].1, [redirected(&["make"], &[(2, Target::Fd(1))]), command(&["less"])]);
+        assert_eq!(list[1].1, [redirected(&["echo", "oops"], &[(1, Target::Fd(2))])]);
+        assert_eq!(list[2].1, [redirected(&["echo", "x"], &[(1, Target::Append("log".into()))])]);
+
+        // Only unquoted digits right before the operator are a descriptor
+        let list = parse("echo 2 >f; echo a2>f; echo '2'>f; > empty").unwrap();
+        assert_eq!(list[0].1, [redirected(&["echo", "2"], &[(1, Target::Write("f".into()))])]);
+        assert_eq!(list[1].1, [redirected(&["echo", "a2"], &[(1, Target::Write("f".into()))])]);
+        assert_eq!(list[2].1, [redirected(&["echo", "2"], &[(1, Target::Write("f".into()))])]);
+        assert_eq!(list[3].1, [redirected(&[], &[(1, Target::Write("empty".into()))])]);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(parse("echo 'open"), Err(Error::UnterminatedQuote('\'')));
+        assert_eq!(parse("echo \"open"), Err(Error::UnterminatedQuote('"')));
+        assert_eq!(parse("| ls"), Err(Error::Unexpected("|".into())));
+        assert_eq!(parse("ls ; ; ls"), Err(Error::Unexpected(";".into())));
+        assert_eq!(parse("ls &&"), Err(Error::UnexpectedEnd));
+        assert_eq!(parse("ls |"), Err(Error::UnexpectedEnd));
+        assert_eq!(parse("ls >"), Err(Error::UnexpectedEnd));
+        assert_eq!(parse("ls > | sort"), Err(Error::Unexpected("|".into())));
+        assert_eq!(parse("ls 2>&x"), Err(Error::Unexpected("x".into())));
+        assert_eq!(parse("sleep 1 &"), Err(Error::Unexpected("&".into())));
+        assert_eq!(parse("ls |").unwrap_err().to_string(), "syntax error: unexpected end of line");
+    }
+
+    #[test]
+    fn last_word_of_a_partial_line() {
+        assert_eq!(last_word("cat my\\ no"), (4, "my no".into()));
+        assert_eq!(last_word("cat 'my no"), (4, "my no".into()));
+        assert_eq!(last_word("echo \"a b\" x|\"c\\\"d"), (13, "c\"d".into()));
+        assert_eq!(last_word("ls ~/s"), (3, "~/s".into()));
+        assert_eq!(last_word("ls "), (3, "".into()));
+        assert_eq!(last_word(""), (0, "".into()));
+    }
+
+    #[test]
+    fn escaped_words_parse_back() {
+        let word = "my notes $it's \"a|b\" & c;d <e> \\";
+        assert_eq!(escape("my notes.txt"), "my\\ notes.txt");
+        assert_eq!(words(&format!("echo {}", escape(word))), ["echo", word]);
+    }
+}
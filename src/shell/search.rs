@@ -0,0 +1,106 @@
+/// Incremental search of the history opened with Ctrl+R, it looks for the query in
+/// entries older than the current match
+#[derive(Debug, Default)]
+pub struct Search {
+    query: String,
+    /// Index in the history of the entry found
+    matched: Option<usize>,
+    /// The last search found nothing, the previous match is still shown
+    failed: bool,
+}
+
+impl Search {
+    /// The query and the entry found, for the prompt of the search
+    pub fn label(&self) -> String {
+        let failed = if self.failed { "failed " } else { "" };
+        format!("({}reverse-i-search)`{}': ", failed, self.query)
+    }
+
+    /// The entry found split around the query, all of it comes first when the search
+    /// failed and the entry does not contain the query
+    pub fn entry<'a>(&self, entries: &'a [String]) -> Option<(&'a str, &'a str, &'a str)> {
+        let entry = entries.get(self.matched?)?;
+        let (start, end) = match entry.find(&self.query) {
+            Some(start) => (start, start + self.query.len()),
+            None => (entry.len(), entry.len()),
+        };
+        Some((&entry[..start], &entry[start..end], &entry[end..]))
+    }
+
+    /// Adds to the query, the current match is kept if it still contains it
+    pub fn push(&mut self, c: char, entries: &[String]) {
+        self.query.push(c);
+        self.find(entries, self.matched.map_or(entries.len(), |matched| matched + 1));
+    }
+
+    /// Removes the last character of the query and searches again from the newest entry
+    pub fn pop(&mut self, entries: &[String]) {
+        self.query.pop();
+        self.find(entries, entries.len());
+    }
+
+    /// Moves on to an older entry containing the query
+    pub fn older(&mut self, entries: &[String]) {
+        self.find(entries, self.matched.unwrap_or(entries.len()));
+    }
+
+    /// Looks for the newest entry before `before` containing the query
+    fn find(&mut self, entries: &[String], before: usize) {
+        if self.query.is_empty() {
+            self.matched = None;
+            self.failed = false;
+            return;
+        }
+        match entries[..before].iter().rposition(|entry| entry.contains(&self.query)) {
+            Some(matched) => {
+                self.matched = Some(matched);
+                self.failed = false;
+            }
+            None => self.failed = true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<String> {
+        ["cargo build", "ls", "cargo test", "echo cat"].map(String::from).to_vec()
+    }
+
+    #[test]
+    fn query_finds_newest_entry_first() {
+        let entries = entries();
+        let mut search = Search::default();
+        search.push('c', &entries);
+        assert_eq!(search.entry(&entries), Some(("e", "c", "ho cat")));
+        search.push('a', &entries);
+        assert_eq!(search.entry(&entries), Some(("echo ", "ca", "t")));
+        search.push('r', &entries);
+        assert_eq!(search.entry(&entries), Some(("", "car", "go test")));
+
+        search.pop(&entries);
+        assert_eq!(search.entry(&entries), Some(("echo ", "ca", "t")));
+        assert_eq!(search.label(), "(reverse-i-search)`ca': ");
+    }
+
+    #[test]
+    fn older_matches_until_none_is_left() {
+        let entries = entries();
+        let mut search = Search::default();
+        for c in "cargo".chars() {
+            search.push(c, &entries);
+        }
+        assert_eq!(search.entry(&entries), Some(("", "cargo", " test")));
+        search.older(&entries);
+        assert_eq!(search.entry(&entries), Some(("", "cargo", " build")));
+
+        // The last match stays when there is no older one
+        search.older(&entries);
+        assert_eq!(search.entry(&entries), Some(("", "cargo", " build")));
+        assert_eq!(search.label(), "(failed reverse-i-search)`cargo': ");
+        search.push('x', &entries);
+        assert_eq!(search.entry(&entries), Some(("cargo build", "", "")));
+    }
+}
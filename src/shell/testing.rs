@@ -0,0 +1,37 @@
+use std::{
+    env, fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Empty directory for a test, removed with its contents when dropped, also when the
+/// test panics. The path is canonical so it compares equal to what the shell reports
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` tells apart the directories of the tests, each call gets a new one
+    pub fn new(name: &str) -> TempDir {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        let dir = format!("terminal-emulator-{}-{}-{}", name, std::process::id(), count);
+        let path = env::temp_dir().join(dir);
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path.canonicalize().unwrap())
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
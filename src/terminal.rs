@@ -0,0 +1,762 @@
+pub mod grid;
+pub mod mouse;
+pub mod parser;
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::shell::Modifiers;
+
+use self::grid::{Cell, Cursor, Flags, Grid};
+use self::mouse::{Encoding, MouseEvent, Tracking};
+use self::parser::{Action, Attr, Color, Erase, Mode, Parser};
+
+const TAB_WIDTH: usize = 8;
+pub const DEFAULT_SCROLLBACK: usize = 10_000;
+
+/// Graphic rendition applied to the printed characters,
+/// `None` colors are the defaults of the color scheme
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Pen {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub flags: Flags,
+}
+
+impl Pen {
+    fn apply(&mut self, attr: Attr) {
+        match attr {
+            Attr::Reset => *self = Pen::default(),
+            Attr::Bold => self.flags.insert(Flags::BOLD),
+            Attr::Dim => self.flags.insert(Flags::DIM),
+            Attr::Italic => self.flags.insert(Flags::ITALIC),
+            Attr::Underline => self.flags.insert(Flags::UNDERLINE),
+            Attr::Blink => self.flags.insert(Flags::BLINK),
+            Attr::Inverse => self.flags.insert(Flags::INVERSE),
+            Attr::Hidden => self.flags.insert(Flags::HIDDEN),
+            Attr::Strike => self.flags.insert(Flags::STRIKE),
+            Attr::CancelBoldDim => {
+                self.flags.remove(Flags::BOLD);
+                self.flags.remove(Flags::DIM);
+            }
+            Attr::CancelItalic => self.flags.remove(Flags::ITALIC),
+            Attr::CancelUnderline => self.flags.remove(Flags::UNDERLINE),
+            Attr::CancelBlink => self.flags.remove(Flags::BLINK),
+            Attr::CancelInverse => self.flags.remove(Flags::INVERSE),
+            Attr::CancelHidden => self.flags.remove(Flags::HIDDEN),
+            Attr::CancelStrike => self.flags.remove(Flags::STRIKE),
+            Attr::Foreground(color) => self.fg = Some(color),
+            Attr::Background(color) => self.bg = Some(color),
+            Attr::DefaultForeground => self.fg = None,
+            Attr::DefaultBackground => self.bg = None,
+        }
+    }
+
+    fn cell(&self, c: char) -> Cell {
+        Cell { c, zerowidth: vec![], fg: self.fg, bg: self.bg, flags: self.flags }
+    }
+
+    fn blank(&self) -> Cell {
+        self.cell(' ').blank()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Modes {
+    pub insert: bool,
+    pub line_feed_new_line: bool,
+    pub cursor_keys: bool,
+    pub origin: bool,
+    pub auto_wrap: bool,
+    pub show_cursor: bool,
+    pub bracketed_paste: bool,
+    /// The alternate screen is shown instead of the main one
+    pub alternate_screen: bool,
+    pub mouse_tracking: Tracking,
+    pub mouse_encoding: Encoding,
+}
+
+impl Default for Modes {
+    fn default() -> Self {
+        Modes {
+            insert: false,
+            line_feed_new_line: false,
+            cursor_keys: false,
+            origin: false,
+            auto_wrap: true,
+            show_cursor: true,
+            bracketed_paste: false,
+            alternate_screen: false,
+            mouse_tracking: Tracking::Off,
+            mouse_encoding: Encoding::X10,
+        }
+    }
+}
+
+/// State of the emulated terminal, built from the output of the program running in it
+pub struct Terminal {
+    parser: Parser,
+    grid: Grid,
+    /// The main screen while the alternate one is shown and the other way round
+    inactive_grid: Grid,
+    saved_cursor: (Cursor, Pen),
+    pen: Pen,
+    modes: Modes,
+    title: String,
+    responses: Vec<u8>,
+}
+
+impl Terminal {
+    pub fn new(cols: usize, rows: usize) -> Terminal {
+        Terminal::with_scrollback(cols, rows, DEFAULT_SCROLLBACK)
+    }
+
+    /// Terminal keeping at most `scrollback` lines that left the top of the screen
+    pub fn with_scrollback(cols: usize, rows: usize, scrollback: usize) -> Terminal {
+        Terminal {
+            parser: Parser::new(),
+            grid: Grid::new(cols, rows, scrollback),
+            // Lines leaving the alternate screen are not kept
+            inactive_grid: Grid::new(cols, rows, 0),
+            saved_cursor: (Cursor::default(), Pen::default()),
+            pen: Pen::default(),
+            modes: Modes::default(),
+            title: String::new(),
+            responses: vec![],
+        }
+    }
+
+    pub fn advance(&mut self, bytes: &[u8]) {
+        for action in self.parser.parse(bytes) {
+            self.perform(action);
+        }
+    }
+
+    pub fn perform(&mut self, action: Action) {
+        let Cursor { row, col } = self.grid.cursor();
+        let blank = self.pen.blank();
+        match action {
+            Action::Print(c) => self.print(c),
+            Action::Bell => (),
+            Action::Backspace => self.grid.move_to(row, col.saturating_sub(1)),
+            Action::Tab => self.grid.move_to(row, (col / TAB_WIDTH + 1) * TAB_WIDTH),
+            Action::LineFeed => {
+                if self.modes.line_feed_new_line {
+                    self.grid.move_to(row, 0);
+                }
+                self.grid.index(blank);
+            }
+            Action::CarriageReturn => self.grid.move_to(row, 0),
+            Action::CursorUp(n) => self.grid.move_to(self.up(row, n), col),
+            Action::CursorDown(n) => self.grid.move_to(self.down(row, n), col),
+            Action::CursorForward(n) => self.grid.move_to(row, col + n),
+            Action::CursorBackward(n) => self.grid.move_to(row, col.saturating_sub(n)),
+            Action::CursorNextLine(n) => self.grid.move_to(self.down(row, n), 0),
+            Action::CursorPrecedingLine(n) => self.grid.move_to(self.up(row, n), 0),
+            Action::CursorColumn(col) => self.grid.move_to(row, col),
+            Action::CursorRow(row) => self.move_to_origin(row, col),
+            Action::CursorPosition { row, col } => self.move_to_origin(row, col),
+            Action::SaveCursor => self.saved_cursor = (self.grid.cursor(), self.pen),
+            Action::RestoreCursor => {
+                let (cursor, pen) = self.saved_cursor;
+                let region = self.grid.scroll_region();
+                // A cursor saved outside the scroll region comes back into it in origin mode
+                let row = match self.modes.origin {
+                    true => cursor.row.clamp(region.start, region.end - 1),
+                    false => cursor.row,
+                };
+                self.grid.move_to(row, cursor.col);
+                self.pen = pen;
+            }
+            Action::Index => self.grid.index(blank),
+            Action::ReverseIndex => self.grid.reverse_index(blank),
+            Action::NextLine => {
+                self.grid.move_to(row, 0);
+                self.grid.index(blank);
+            }
+            Action::EraseInDisplay(erase) => self.grid.erase_display(erase, blank),
+            Action::EraseInLine(erase) => self.grid.erase_line(erase, blank),
+            Action::EraseChars(n) => self.grid.erase_chars(n, blank),
+            Action::InsertChars(n) => self.grid.insert_chars(n, blank),
+            Action::DeleteChars(n) => self.grid.delete_chars(n, blank),
+            Action::InsertLines(n) => self.grid.insert_lines(n, blank),
+            Action::DeleteLines(n) => self.grid.delete_lines(n, blank),
+            Action::ScrollUp(n) => self.grid.scroll_up(n, blank),
+            Action::ScrollDown(n) => self.grid.scroll_down(n, blank),
+            Action::SetScrollRegion { top, bottom } => {
+                self.grid.set_scroll_region(top, bottom.unwrap_or(self.grid.rows()));
+                self.move_to_origin(0, 0);
+            }
+            Action::Sgr(attr) => self.pen.apply(attr),
+            Action::SetMode(mode) => self.set_mode(mode, true),
+            Action::ResetMode(mode) => self.set_mode(mode, false),
+            Action::DeviceStatus => self.responses.extend(b"\x1b[0n"),
+            Action::CursorPositionReport => {
+                let row = match self.modes.origin {
+                    true => row.saturating_sub(self.grid.scroll_region().start),
+                    false => row,
+                };
+                let report = format!("\x1b[{};{}R", row + 1, col + 1);
+                self.responses.extend(report.as_bytes());
+            }
+            // Identify as a VT102
+            Action::DeviceAttributes => self.responses.extend(b"\x1b[?6c"),
+            Action::SetTitle(title) => self.title = title,
+            Action::Reset => {
+                self.switch_screen(false);
+                self.pen = Pen::default();
+                self.modes = Modes::default();
+                self.saved_cursor = (Cursor::default(), Pen::default());
+                self.grid.set_scroll_region(0, self.grid.rows());
+                self.grid.move_to(0, 0);
+                self.grid.erase_display(Erase::All, Cell::default());
+            }
+        }
+    }
+
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        self.grid.resize(cols, rows);
+        self.inactive_grid.resize(cols, rows);
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// Moves the view through the scrollback, see [`Grid::scroll_display`]
+    pub fn scroll_display(&mut self, lines: isize) {
+        self.grid.scroll_display(lines);
+    }
+
+    pub fn reset_display_offset(&mut self) {
+        self.grid.reset_display_offset();
+    }
+
+    pub fn pen(&self) -> Pen {
+        self.pen
+    }
+
+    pub fn modes(&self) -> Modes {
+        self.modes
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Pasted text as it is written to the program, between markers in bracketed paste
+    /// mode. Control characters other than tabs and line breaks are dropped so the text
+    /// cannot end the paste early or run escape sequences
+    pub fn paste(&self, text: &str) -> Vec<u8> {
+        let text = text.replace("\x1b[200~", "").replace("\x1b[201~", "").replace("\r\n", "\r");
+        let text: String = text
+            .chars()
+            .filter_map(|c| match c {
+                // Enter sends a carriage return
+                '\n' | '\r' => Some('\r'),
+                '\t' => Some('\t'),
+                c if c.is_control() => None,
+                c => Some(c),
+            })
+            .collect();
+        match self.modes.bracketed_paste {
+            true => format!("\x1b[200~{}\x1b[201~", text).into_bytes(),
+            false => text.into_bytes(),
+        }
+    }
+
+    /// Bytes reporting a mouse event at `col`, `row` of the screen in the tracking mode and
+    /// encoding the program asked for, `None` if it did not ask for this event
+    pub fn mouse_report(
+        &self,
+        event: MouseEvent,
+        col: usize,
+        row: usize,
+        modifiers: Modifiers,
+    ) -> Option<Vec<u8>> {
+        let Modes { mouse_tracking, mouse_encoding, .. } = self.modes;
+        mouse::report(event, col, row, modifiers, mouse_tracking, mouse_encoding)
+    }
+
+    /// Replies to queries such as the cursor position report, to be written back to the program
+    pub fn take_responses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.responses)
+    }
+
+    /// Writes `c` at the cursor as wide as it is on the terminal, marks and joiners go
+    /// into the cell before it
+    fn print(&mut self, c: char) {
+        let width = c.width().unwrap_or(1);
+        match self.grid.previous_cell_mut() {
+            // Combining marks and joiners belong to the character before them
+            Some(cell) if width == 0 || cell.extended_by(c) => {
+                cell.zerowidth.push(c);
+                return;
+            }
+            None if width == 0 => return,
+            _ => (),
+        }
+        let mut cell = self.pen.cell(c);
+        if width == 2 {
+            cell.flags.insert(Flags::WIDE);
+        }
+        self.grid.write(cell, self.modes.auto_wrap, self.modes.insert);
+    }
+
+    /// Row `n` lines above, stopping at the top of the scroll region when inside it
+    fn up(&self, row: usize, n: usize) -> usize {
+        let region = self.grid.scroll_region();
+        let top = if row >= region.start { region.start } else { 0 };
+        row.saturating_sub(n).max(top)
+    }
+
+    /// Row `n` lines below, stopping at the bottom of the scroll region when inside it
+    fn down(&self, row: usize, n: usize) -> usize {
+        let region = self.grid.scroll_region();
+        let bottom = if row < region.end { region.end } else { self.grid.rows() };
+        (row + n).min(bottom - 1)
+    }
+
+    /// Absolute cursor positioning, relative to the scroll region in origin mode
+    fn move_to_origin(&mut self, row: usize, col: usize) {
+        if self.modes.origin {
+            let region = self.grid.scroll_region();
+            self.grid.move_to((region.start + row).min(region.end - 1), col);
+        } else {
+            self.grid.move_to(row, col);
+        }
+    }
+
+    /// Shows the alternate screen or the main one, the cursor stays where it is
+    fn switch_screen(&mut self, alternate: bool) {
+        if alternate == self.modes.alternate_screen {
+            return;
+        }
+        let cursor = self.grid.cursor();
+        std::mem::swap(&mut self.grid, &mut self.inactive_grid);
+        self.grid.move_to(cursor.row, cursor.col);
+        self.modes.alternate_screen = alternate;
+    }
+
+    fn set_mode(&mut self, mode: Mode, enable: bool) {
+        match mode {
+            Mode::Insert => self.modes.insert = enable,
+            Mode::LineFeedNewLine => self.modes.line_feed_new_line = enable,
+            Mode::CursorKeys => self.modes.cursor_keys = enable,
+            Mode::Origin => {
+                self.modes.origin = enable;
+                self.move_to_origin(0, 0);
+            }
+            Mode::AutoWrap => self.modes.auto_wrap = enable,
+            Mode::ShowCursor => self.modes.show_cursor = enable,
+            Mode::BracketedPaste => self.modes.bracketed_paste = enable,
+            Mode::AlternateScreen => self.switch_screen(enable),
+            Mode::AlternateScreenClear => {
+                if !enable && self.modes.alternate_screen {
+                    self.grid.erase_display(Erase::All, self.pen.blank());
+                }
+                self.switch_screen(enable);
+            }
+            Mode::AlternateScreenSaveCursor if enable => {
+                self.perform(Action::SaveCursor);
+                self.switch_screen(true);
+                self.grid.erase_display(Erase::All, self.pen.blank());
+            }
+            Mode::AlternateScreenSaveCursor => {
+                self.switch_screen(false);
+                self.perform(Action::RestoreCursor);
+            }
+            Mode::MouseClick | Mode::MouseDrag | Mode::MouseMotion if !enable => {
+                self.modes.mouse_tracking = Tracking::Off;
+            }
+            Mode::MouseClick => self.modes.mouse_tracking = Tracking::Click,
+            Mode::MouseDrag => self.modes.mouse_tracking = Tracking::Drag,
+            Mode::MouseMotion => self.modes.mouse_tracking = Tracking::Motion,
+            Mode::SgrMouse | Mode::UrxvtMouse if !enable => {
+                self.modes.mouse_encoding = Encoding::X10;
+            }
+            Mode::SgrMouse => self.modes.mouse_encoding = Encoding::Sgr,
+            Mode::UrxvtMouse => self.modes.mouse_encoding = Encoding::Urxvt,
+            Mode::Unknown { .. } => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(cols: usize, rows: usize, bytes: &[u8]) -> Terminal {
+        let mut terminal = Terminal::new(cols, rows);
+        terminal.advance(bytes);
+        terminal
+    }
+
+    fn screen(terminal: &Terminal) -> Vec<String> {
+        let grid = terminal.grid();
+        (0..grid.rows()).map(|row| grid.row(row).text()).collect()
+    }
+
+    fn lines(terminal: &Terminal) -> Vec<String> {
+        let history = terminal.grid().history().iter().map(|row| row.text());
+        history.chain(screen(terminal)).collect()
+    }
+
+    // Recorded from bash 5.1 with a colored PS1, running `ls --color` in a directory with
+    // one folder and one file
+    const BASH_LS: &[u8] = b"\x1b]0;user@host: ~/src\x07\x1b[01;32muser@host\x1b[00m:\x1b[01;34m~/src\x1b[00m$ ls --color\r\n\x1b[0m\x1b[01;34mdocs\x1b[0m  notes.txt\r\n\x1b]0;user@host: ~/src\x07\x1b[01;32muser@host\x1b[00m:\x1b[01;34m~/src\x1b[00m$ ";
+
+    #[test]
+    fn bash_prompt_and_colored_ls() {
+        let terminal = run(40, 4, BASH_LS);
+        assert_eq!(
+            screen(&terminal),
+            vec!["user@host:~/src$ ls --color", "docs  notes.txt", "user@host:~/src$", ""]
+        );
+        assert_eq!(terminal.grid().cursor(), Cursor { row: 2, col: 17 });
+        assert_eq!(terminal.title(), "user@host: ~/src");
+        assert_eq!(terminal.pen(), Pen::default());
+    }
+
+    // Recorded from bash: typing `abc`, two backspaces, then `x`
+    #[test]
+    fn bash_line_editing_echo() {
+        let terminal = run(20, 2, b"$ abc\x08 \x08\x08 \x08x");
+        assert_eq!(screen(&terminal), vec!["$ ax", ""]);
+        assert_eq!(terminal.grid().cursor(), Cursor { row: 0, col: 4 });
+    }
+
+    // Output of `clear` with TERM=xterm-256color
+    #[test]
+    fn clear_screen() {
+        let terminal = run(10, 3, b"one\r\ntwo\r\nthree\x1b[H\x1b[2J\x1b[3J");
+        assert_eq!(screen(&terminal), vec!["", "", ""]);
+        assert_eq!(terminal.grid().cursor(), Cursor::default());
+        assert!(terminal.grid().history().is_empty());
+    }
+
+    #[test]
+    fn wraps_and_scrolls_into_history() {
+        let terminal = run(4, 2, b"abcdefgh\r\nij");
+        assert_eq!(lines(&terminal), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(screen(&terminal), vec!["efgh", "ij"]);
+    }
+
+    #[test]
+    fn pending_wrap_is_cancelled_by_carriage_return() {
+        let terminal = run(4, 2, b"abcd\rx");
+        assert_eq!(screen(&terminal), vec!["xbcd", ""]);
+    }
+
+    #[test]
+    fn no_wrap_overwrites_last_column() {
+        let terminal = run(4, 2, b"\x1b[?7labcdef");
+        assert_eq!(screen(&terminal), vec!["abcf", ""]);
+    }
+
+    // A readline redraw after moving the cursor into the middle of the line and inserting
+    #[test]
+    fn insert_and_delete_chars() {
+        let terminal = run(10, 1, b"hello\x1b[3D\x1b[@X\x1b[2C\x1b[P");
+        assert_eq!(screen(&terminal), vec!["heXll"]);
+        let terminal = run(10, 1, b"hello\x1b[4G\x1b[K!\x1b[1G\x1b[2X");
+        assert_eq!(screen(&terminal), vec!["  l!"]);
+    }
+
+    #[test]
+    fn insert_and_delete_lines() {
+        let terminal = run(3, 3, b"a\r\nb\r\nc\x1b[2;1H\x1b[L");
+        assert_eq!(screen(&terminal), vec!["a", "", "b"]);
+        let terminal = run(3, 3, b"a\r\nb\r\nc\x1b[1;1H\x1b[2M");
+        assert_eq!(screen(&terminal), vec!["c", "", ""]);
+    }
+
+    #[test]
+    fn reverse_index_at_top_scrolls_down() {
+        let terminal = run(3, 2, b"a\r\nb\x1b[H\x1bMc");
+        assert_eq!(screen(&terminal), vec!["c", "a"]);
+    }
+
+    #[test]
+    fn save_and_restore_cursor() {
+        let terminal = run(10, 3, b"ab\x1b7\x1b[3;5Hx\x1b8y");
+        assert_eq!(screen(&terminal), vec!["aby", "", "    x"]);
+    }
+
+    #[test]
+    fn sgr_and_modes_are_tracked() {
+        let terminal = run(10, 1, b"\x1b[1;38;5;200m\x1b[?1h\x1b[?25l");
+        assert_eq!(
+            terminal.pen(),
+            Pen { fg: Some(Color::Indexed(200)), bg: None, flags: Flags::BOLD }
+        );
+        assert!(terminal.modes().cursor_keys);
+        assert!(!terminal.modes().show_cursor);
+    }
+
+    #[test]
+    fn paste_is_bracketed_and_stripped() {
+        let mut terminal = run(10, 1, b"");
+        let text = "ls\r\ncd /\x1b[201~\x1b[31m\x07\tx\n";
+        assert_eq!(terminal.paste(text), b"ls\rcd /[31m\tx\r");
+
+        terminal.advance(b"\x1b[?2004h");
+        assert_eq!(terminal.paste(text), b"\x1b[200~ls\rcd /[31m\tx\r\x1b[201~");
+        terminal.advance(b"\x1b[?2004l");
+        assert_eq!(terminal.paste("a"), b"a");
+    }
+
+    // What less does with TERM=xterm-256color: 1049 around the pager, then back at the prompt
+    #[test]
+    fn alternate_screen_restores_main_screen() {
+        let mut terminal = run(8, 3, b"a\r\nb\r\n$ less");
+        terminal.advance(b"\x1b[?1049h\x1b[22;0;0t\x1b[H\x1b[2Jpage 1\r\n1\r\n2\r\n3\r\n(END)");
+        assert!(terminal.modes().alternate_screen);
+        assert_eq!(screen(&terminal), vec!["2", "3", "(END)"]);
+        // Lines scrolled off the alternate screen are dropped
+        assert_eq!(terminal.grid().history().len(), 0);
+
+        terminal.advance(b"\r\x1b[K\x1b[?1049l\x1b[23;0;0t\r\n$ ");
+        assert!(!terminal.modes().alternate_screen);
+        assert_eq!(lines(&terminal), vec!["a", "b", "$ less", "$"]);
+        assert_eq!(terminal.grid().cursor(), Cursor { row: 2, col: 2 });
+    }
+
+    #[test]
+    fn alternate_screen_modes_47_and_1047() {
+        let mut terminal = run(5, 2, b"main\x1b[?47hx");
+        assert_eq!(screen(&terminal), vec!["    x", ""]);
+        terminal.advance(b"\x1b[?47l\x1b[?47h");
+        assert_eq!(screen(&terminal), vec!["    x", ""]);
+
+        terminal.advance(b"\x1b[?47l\x1b[?1047h\x1b[?1047l");
+        assert_eq!(screen(&terminal), vec!["main", ""]);
+        terminal.advance(b"\x1b[?1047h");
+        assert_eq!(screen(&terminal), vec!["", ""]);
+    }
+
+    // htop enables 1000 and 1006, mc 1002 without an encoding, vim with mouse=a 1002
+    // and 1015 depending on ttymouse
+    #[test]
+    fn mouse_reports_follow_tracking_and_encoding() {
+        use mouse::{Button, MouseEvent::*};
+        let none = Modifiers::default();
+        let ctrl = Modifiers { ctrl: true, ..none };
+
+        let mut terminal = run(300, 5, b"");
+        assert_eq!(terminal.mouse_report(Press(Button::Left), 0, 0, none), None);
+
+        terminal.advance(b"\x1b[?1000h\x1b[?1006h");
+        assert_eq!(
+            terminal.mouse_report(Press(Button::Left), 4, 2, none).unwrap(),
+            b"\x1b[<0;5;3M"
+        );
+        assert_eq!(
+            terminal.mouse_report(Release(Button::Right), 4, 2, ctrl).unwrap(),
+            b"\x1b[<18;5;3m"
+        );
+        assert_eq!(terminal.mouse_report(WheelDown, 0, 0, none).unwrap(), b"\x1b[<65;1;1M");
+        assert_eq!(terminal.mouse_report(Motion(Some(Button::Left)), 1, 1, none), None);
+
+        terminal.advance(b"\x1b[?1006l\x1b[?1002h");
+        assert_eq!(
+            terminal.mouse_report(Press(Button::Middle), 0, 1, none).unwrap(),
+            b"\x1b[M!!\""
+        );
+        assert_eq!(
+            terminal.mouse_report(Release(Button::Middle), 0, 1, none).unwrap(),
+            b"\x1b[M#!\""
+        );
+        assert_eq!(
+            terminal.mouse_report(Motion(Some(Button::Left)), 1, 1, none).unwrap(),
+            b"\x1b[M@\"\""
+        );
+        assert_eq!(terminal.mouse_report(Motion(None), 1, 1, none), None);
+        // X10 cannot encode columns past 223
+        assert_eq!(terminal.mouse_report(Press(Button::Left), 250, 1, none), None);
+
+        terminal.advance(b"\x1b[?1003h\x1b[?1015h");
+        assert_eq!(terminal.mouse_report(Motion(None), 250, 1, none).unwrap(), b"\x1b[67;251;2M");
+
+        terminal.advance(b"\x1b[?1003l");
+        assert_eq!(terminal.mouse_report(Press(Button::Left), 0, 0, none), None);
+    }
+
+    #[test]
+    fn answers_queries() {
+        let mut terminal = run(10, 5, b"\x1b[3;4H\x1b[6n\x1b[c");
+        assert_eq!(terminal.take_responses(), b"\x1b[3;4R\x1b[?6c");
+        assert!(terminal.take_responses().is_empty());
+
+        // Restored above the scroll region in origin mode, the cursor goes to its top
+        let mut terminal = run(10, 8, b"\x1b7\x1b[3;5r\x1b[?6h\x1b8\x1b[6n");
+        assert_eq!(terminal.take_responses(), b"\x1b[1;1R");
+        assert_eq!(terminal.grid().cursor().row, 2);
+    }
+
+    #[test]
+    fn tabs_stop_every_eight_columns() {
+        let terminal = run(20, 1, b"a\tb\tc\td");
+        assert_eq!(screen(&terminal), vec!["a       b       c  d"]);
+    }
+
+    #[test]
+    fn resize_keeps_cursor_line_on_screen() {
+        let mut terminal = run(5, 3, b"a\r\nb\r\nc");
+        terminal.resize(3, 2);
+        assert_eq!(screen(&terminal), vec!["b", "c"]);
+        assert_eq!(lines(&terminal), vec!["a", "b", "c"]);
+        assert_eq!(terminal.grid().cursor(), Cursor { row: 1, col: 1 });
+    }
+
+    #[test]
+    fn wrapped_rows_are_flagged() {
+        let terminal = run(4, 3, b"abcdef\r\nxy");
+        let grid = terminal.grid();
+        assert!(grid.row(0).wrapped);
+        assert!(!grid.row(1).wrapped);
+        assert!(!grid.row(2).wrapped);
+    }
+
+    // What less and vim do to scroll the text while keeping a status line at the bottom
+    #[test]
+    fn scroll_region_keeps_status_line() {
+        let terminal =
+            run(6, 4, b"\x1b[4;1Hstatus\x1b[1;3r\x1b[1;1Ha\r\nb\r\nc\r\nd\x1bM\x1bM\x1bM\rz");
+        assert_eq!(screen(&terminal), vec!["z", "b", "c", "status"]);
+        assert_eq!(lines(&terminal), vec!["a", "z", "b", "c", "status"]);
+        let terminal = run(6, 4, b"\x1b[2;3ra\r\nb\r\nc\x1b[2;1H\x1b[M");
+        assert_eq!(screen(&terminal), vec!["a", "c", "", ""]);
+    }
+
+    #[test]
+    fn origin_mode_positions_relative_to_region() {
+        let terminal = run(6, 5, b"\x1b[2;4r\x1b[?6h\x1b[10;2Hx");
+        assert_eq!(terminal.grid().cursor(), Cursor { row: 3, col: 2 });
+    }
+
+    #[test]
+    fn cells_keep_attributes_and_erase_uses_background() {
+        let terminal = run(4, 1, b"\x1b[1;31ma\x1b[0;44m\x1b[K");
+        let row = terminal.grid().row(0);
+        assert_eq!(
+            row.cells[0],
+            Cell { c: 'a', fg: Some(Color::Named(1)), flags: Flags::BOLD, ..Cell::default() }
+        );
+        assert_eq!(row.cells[1], Cell { bg: Some(Color::Named(4)), ..Cell::default() });
+    }
+
+    #[test]
+    fn scrollback_is_bounded() {
+        let mut terminal = Terminal::with_scrollback(3, 2, 2);
+        terminal.advance(b"1\r\n2\r\n3\r\n4\r\n5");
+        assert_eq!(lines(&terminal), vec!["2", "3", "4", "5"]);
+        assert_eq!(terminal.grid().absolute_row(0), 3);
+    }
+
+    #[test]
+    fn scrolled_view_stays_on_the_same_lines() {
+        let mut terminal = run(3, 2, b"1\r\n2\r\n3\r\n4");
+        terminal.scroll_display(1);
+        let visible = |terminal: &Terminal| -> Vec<String> {
+            (0..2).map(|row| terminal.grid().visible_row(row).text()).collect()
+        };
+        assert_eq!(visible(&terminal), vec!["2", "3"]);
+        terminal.advance(b"\r\n5");
+        assert_eq!(visible(&terminal), vec!["2", "3"]);
+        terminal.scroll_display(10);
+        assert_eq!(visible(&terminal), vec!["1", "2"]);
+        terminal.reset_display_offset();
+        assert_eq!(visible(&terminal), vec!["4", "5"]);
+    }
+
+    #[test]
+    fn history_is_resized_with_the_screen() {
+        let mut terminal = run(3, 2, b"abc\r\n2\r\n3\r\n4");
+        terminal.resize(5, 2);
+        terminal.scroll_display(2);
+        let grid = terminal.grid();
+        assert_eq!(grid.visible_row(0).text(), "abc");
+        assert!((0..2).all(|row| grid.visible_row(row).cells.len() == 5));
+
+        terminal.resize(2, 2);
+        terminal.scroll_display(2);
+        let grid = terminal.grid();
+        assert_eq!(grid.visible_row(0).text(), "ab");
+        assert!((0..2).all(|row| grid.visible_row(row).cells.len() == 2));
+    }
+
+    #[test]
+    fn multibyte_characters_wrap_whole() {
+        let terminal = run(4, 3, "aéü€ñ😀x".as_bytes());
+        assert_eq!(screen(&terminal), vec!["aéü€", "ñ😀x", ""]);
+    }
+
+    #[test]
+    fn combining_marks_join_the_previous_cell() {
+        let terminal = run(4, 2, "e\u{301}a\u{308}\u{323}o".as_bytes());
+        let row = terminal.grid().row(0);
+        assert_eq!(row.cells[0].text(), "e\u{301}");
+        assert_eq!(row.cells[1].text(), "a\u{308}\u{323}");
+        assert_eq!(row.cells[2].text(), "o");
+        assert_eq!(terminal.grid().cursor(), Cursor { row: 0, col: 3 });
+    }
+
+    #[test]
+    fn combining_mark_after_wrap_stays_on_the_wrapped_cell() {
+        let terminal = run(2, 2, "abc\u{301}".as_bytes());
+        assert_eq!(screen(&terminal), vec!["ab", "c\u{301}"]);
+        let terminal = run(2, 2, "ab\u{301}c".as_bytes());
+        assert_eq!(screen(&terminal), vec!["ab\u{301}", "c"]);
+    }
+
+    #[test]
+    fn emoji_sequences_take_one_cell() {
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        let flag = "\u{1f1e9}\u{1f1ea}";
+        let terminal = run(5, 1, format!("{family}{flag}!").as_bytes());
+        let row = terminal.grid().row(0);
+        assert_eq!(row.cells[0].text(), family);
+        assert_eq!(row.cells[2].text(), flag);
+        assert_eq!(row.cells[3].c, '!');
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        let terminal = run(6, 2, "a中b".as_bytes());
+        let row = terminal.grid().row(0);
+        assert!(row.cells[1].flags.contains(Flags::WIDE));
+        assert!(row.cells[2].flags.contains(Flags::WIDE_SPACER));
+        assert_eq!(row.cells[3].c, 'b');
+        assert_eq!(row.text(), "a中b");
+        assert_eq!(terminal.grid().cursor(), Cursor { row: 0, col: 4 });
+    }
+
+    #[test]
+    fn wide_character_in_last_column_wraps() {
+        let terminal = run(3, 2, "ab中".as_bytes());
+        assert_eq!(screen(&terminal), vec!["ab", "中"]);
+        assert!(terminal.grid().row(0).wrapped);
+        let terminal = run(4, 2, "ab中".as_bytes());
+        assert_eq!(screen(&terminal), vec!["ab中", ""]);
+        assert_eq!(terminal.grid().cursor(), Cursor { row: 0, col: 3 });
+        let terminal = run(4, 2, "ab中x".as_bytes());
+        assert_eq!(screen(&terminal), vec!["ab中", "x"]);
+    }
+
+    #[test]
+    fn overwriting_half_of_a_wide_character_blanks_the_other() {
+        let terminal = run(4, 1, "中文\x1b[2Gx".as_bytes());
+        assert_eq!(screen(&terminal), vec![" x文"]);
+        let terminal = run(4, 1, "中文\x1b[1Gx".as_bytes());
+        assert_eq!(screen(&terminal), vec!["x 文"]);
+    }
+
+    #[test]
+    fn zero_width_characters_take_no_column() {
+        let terminal = run(4, 1, "中\u{301}\u{200b}a".as_bytes());
+        let row = terminal.grid().row(0);
+        assert_eq!(row.cells[0].text(), "中\u{301}\u{200b}");
+        assert_eq!(row.cells[2].c, 'a');
+        assert_eq!(terminal.grid().cursor(), Cursor { row: 0, col: 3 });
+    }
+}
@@ -0,0 +1,454 @@
+use std::{collections::VecDeque, ops::Range};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::parser::{Color, Erase};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Flags(u16);
+
+impl Flags {
+    pub const BOLD: Flags = Flags(1);
+    pub const DIM: Flags = Flags(1 << 1);
+    pub const ITALIC: Flags = Flags(1 << 2);
+    pub const UNDERLINE: Flags = Flags(1 << 3);
+    pub const BLINK: Flags = Flags(1 << 4);
+    pub const INVERSE: Flags = Flags(1 << 5);
+    pub const HIDDEN: Flags = Flags(1 << 6);
+    pub const STRIKE: Flags = Flags(1 << 7);
+    /// The character takes this cell and the one after it
+    pub const WIDE: Flags = Flags(1 << 8);
+    /// Second half of a wide character, drawn as part of the cell before it
+    pub const WIDE_SPACER: Flags = Flags(1 << 9);
+
+    pub fn contains(&self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Flags) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Flags) {
+        self.0 &= !other.0;
+    }
+}
+
+/// Character on the screen with its colors, `None` colors are the defaults of the color scheme
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub c: char,
+    /// Combining marks and joined characters that make one grapheme cluster with `c`
+    pub zerowidth: Vec<char>,
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub flags: Flags,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell { c: ' ', zerowidth: vec![], fg: None, bg: None, flags: Flags::default() }
+    }
+}
+
+impl Cell {
+    /// Empty cell keeping the background, used when erasing
+    pub fn blank(&self) -> Cell {
+        Cell { bg: self.bg, ..Cell::default() }
+    }
+
+    /// The grapheme cluster shown in the cell
+    pub fn text(&self) -> String {
+        std::iter::once(self.c).chain(self.zerowidth.iter().copied()).collect()
+    }
+
+    /// Whether `c` continues the grapheme cluster of the cell instead of starting a new one
+    pub fn extended_by(&self, c: char) -> bool {
+        let mut text = self.text();
+        text.push(c);
+        text.graphemes(true).nth(1).is_none()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Row {
+    pub cells: Vec<Cell>,
+    /// The line continues on the next row
+    pub wrapped: bool,
+}
+
+impl Row {
+    fn new(cols: usize, blank: Cell) -> Row {
+        Row { cells: vec![blank; cols], wrapped: false }
+    }
+
+    /// Characters of the row without the trailing blanks
+    pub fn text(&self) -> String {
+        let cells = self.cells.iter().filter(|cell| !cell.flags.contains(Flags::WIDE_SPACER));
+        cells.map(Cell::text).collect::<String>().trim_end().to_string()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cursor {
+    pub row: usize,
+    pub col: usize,
+}
+
+/// Screen of `rows` by `cols` cells and up to `scrollback` lines scrolled off its top
+pub struct Grid {
+    cols: usize,
+    rows: usize,
+    lines: Vec<Row>,
+    history: VecDeque<Row>,
+    scrollback: usize,
+    /// Number of lines that ever left the screen, ties screen rows to absolute line numbers
+    scrolled: usize,
+    /// How many lines of history the view is scrolled back by
+    display_offset: usize,
+    cursor: Cursor,
+    /// Set after writing into the last column, the next character goes to a new line
+    wrap_pending: bool,
+    scroll_region: Range<usize>,
+}
+
+impl Grid {
+    pub fn new(cols: usize, rows: usize, scrollback: usize) -> Grid {
+        let cols = cols.max(1);
+        let rows = rows.max(1);
+        Grid {
+            cols,
+            rows,
+            lines: vec![Row::new(cols, Cell::default()); rows],
+            history: VecDeque::new(),
+            scrollback,
+            scrolled: 0,
+            display_offset: 0,
+            cursor: Cursor::default(),
+            wrap_pending: false,
+            scroll_region: 0..rows,
+        }
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn row(&self, row: usize) -> &Row {
+        &self.lines[row]
+    }
+
+    pub fn history(&self) -> &VecDeque<Row> {
+        &self.history
+    }
+
+    pub fn display_offset(&self) -> usize {
+        self.display_offset
+    }
+
+    /// Row as it is shown in the view scrolled back by the display offset
+    pub fn visible_row(&self, row: usize) -> &Row {
+        match row.checked_sub(self.display_offset) {
+            Some(row) => &self.lines[row],
+            None => &self.history[self.history.len() - self.display_offset + row],
+        }
+    }
+
+    /// Scrolls the view, positive `lines` go back into history
+    pub fn scroll_display(&mut self, lines: isize) {
+        let offset = self.display_offset.saturating_add_signed(lines);
+        self.display_offset = offset.min(self.history.len());
+    }
+
+    pub fn reset_display_offset(&mut self) {
+        self.display_offset = 0;
+    }
+
+    pub fn cursor(&self) -> Cursor {
+        self.cursor
+    }
+
+    pub fn scroll_region(&self) -> Range<usize> {
+        self.scroll_region.clone()
+    }
+
+    pub fn absolute_row(&self, row: usize) -> usize {
+        self.scrolled + row
+    }
+
+    /// Absolute line shown at `row` of the view scrolled back by the display offset
+    pub fn visible_to_absolute(&self, row: usize) -> usize {
+        self.scrolled + row - self.display_offset
+    }
+
+    /// Row of the screen or the history at an absolute line, `None` once it has been
+    /// dropped from the history or is below the screen
+    pub fn line(&self, absolute_row: usize) -> Option<&Row> {
+        match absolute_row.checked_sub(self.scrolled) {
+            Some(row) => self.lines.get(row),
+            None => {
+                let first = self.scrolled - self.history.len();
+                self.history.get(absolute_row.checked_sub(first)?)
+            }
+        }
+    }
+
+    /// Screen row of an absolute line, `None` if it has left the screen
+    pub fn screen_row(&self, absolute_row: usize) -> Option<usize> {
+        absolute_row.checked_sub(self.scrolled).filter(|&row| row < self.rows)
+    }
+
+    pub fn move_to(&mut self, row: usize, col: usize) {
+        self.cursor = Cursor { row: row.min(self.rows - 1), col: col.min(self.cols - 1) };
+        self.wrap_pending = false;
+    }
+
+    /// Puts a character at the cursor and advances it, wrapping to the next line
+    /// when `auto_wrap` is set. A `WIDE` cell is followed by a spacer
+    pub fn write(&mut self, cell: Cell, auto_wrap: bool, insert: bool) {
+        let width = if cell.flags.contains(Flags::WIDE) && self.cols > 1 { 2 } else { 1 };
+        // A wide character that does not fit in the last column goes to the next line
+        let no_room = self.cursor.col + width > self.cols;
+        if self.wrap_pending || (no_room && auto_wrap) {
+            self.lines[self.cursor.row].wrapped = true;
+            self.cursor.col = 0;
+            let blank = cell.blank();
+            self.index(blank);
+        } else if no_room {
+            self.cursor.col = self.cols - width;
+        }
+        self.wrap_pending = false;
+
+        let Cursor { row, col } = self.cursor;
+        let mut cells = vec![cell];
+        if width == 2 {
+            let spacer = Cell { flags: Flags::WIDE_SPACER, ..cells[0].blank() };
+            cells.push(spacer);
+        } else {
+            cells[0].flags.remove(Flags::WIDE);
+        }
+        if insert {
+            let line = &mut self.lines[row].cells;
+            line.truncate(self.cols - width);
+            line.splice(col..col, cells);
+        } else {
+            self.split_wide(row, col);
+            self.split_wide(row, col + width - 1);
+            self.lines[row].cells.splice(col..col + width, cells);
+        }
+
+        if col + width < self.cols {
+            self.cursor.col = col + width;
+        } else {
+            self.cursor.col = self.cols - 1;
+            self.wrap_pending = auto_wrap;
+        }
+    }
+
+    /// Blanks the other half of a wide character about to be partly overwritten at `col`
+    fn split_wide(&mut self, row: usize, col: usize) {
+        let line = &mut self.lines[row].cells;
+        if line[col].flags.contains(Flags::WIDE_SPACER) && col > 0 {
+            line[col - 1] = line[col - 1].blank();
+        } else if line[col].flags.contains(Flags::WIDE) && col + 1 < line.len() {
+            line[col + 1] = line[col + 1].blank();
+        }
+    }
+
+    /// Cell written last, where a combining character arriving next belongs
+    pub fn previous_cell_mut(&mut self) -> Option<&mut Cell> {
+        let Cursor { row, col } = self.cursor;
+        let (row, col) = if self.wrap_pending {
+            (row, col)
+        } else if col > 0 {
+            (row, col - 1)
+        } else if row > 0 && self.lines[row - 1].wrapped {
+            (row - 1, self.cols - 1)
+        } else {
+            return None;
+        };
+        let line = &mut self.lines[row].cells;
+        if line[col].flags.contains(Flags::WIDE_SPACER) && col > 0 {
+            Some(&mut line[col - 1])
+        } else {
+            Some(&mut line[col])
+        }
+    }
+
+    /// Moves down a line, scrolling the region at its bottom
+    pub fn index(&mut self, blank: Cell) {
+        self.wrap_pending = false;
+        if self.cursor.row + 1 == self.scroll_region.end {
+            self.scroll_up(1, blank);
+        } else if self.cursor.row + 1 < self.rows {
+            self.cursor.row += 1;
+        }
+    }
+
+    /// Moves up a line, scrolling the region at its top
+    pub fn reverse_index(&mut self, blank: Cell) {
+        self.wrap_pending = false;
+        if self.cursor.row == self.scroll_region.start {
+            self.scroll_down(1, blank);
+        } else if self.cursor.row > 0 {
+            self.cursor.row -= 1;
+        }
+    }
+
+    /// Scrolls the region up, lines leaving the top of the screen go to history
+    pub fn scroll_up(&mut self, n: usize, blank: Cell) {
+        let region = self.scroll_region.clone();
+        let n = n.min(region.len());
+        let removed: Vec<Row> = self.lines.drain(region.start..region.start + n).collect();
+        if region.start == 0 {
+            self.push_history(removed);
+        }
+        let new_lines = vec![Row::new(self.cols, blank); n];
+        self.lines.splice(region.end - n..region.end - n, new_lines);
+    }
+
+    pub fn scroll_down(&mut self, n: usize, blank: Cell) {
+        let region = self.scroll_region.clone();
+        let n = n.min(region.len());
+        self.lines.drain(region.end - n..region.end);
+        self.lines.splice(region.start..region.start, vec![Row::new(self.cols, blank); n]);
+    }
+
+    /// Sets the rows scrolled by line feeds, `bottom` is exclusive
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let bottom = bottom.min(self.rows);
+        if top + 1 < bottom {
+            self.scroll_region = top..bottom;
+        }
+    }
+
+    pub fn erase_display(&mut self, erase: Erase, blank: Cell) {
+        let Cursor { row, col } = self.cursor;
+        match erase {
+            Erase::ToEnd => {
+                self.clear(row, col..self.cols, &blank);
+                for row in row + 1..self.rows {
+                    self.clear(row, 0..self.cols, &blank);
+                }
+            }
+            Erase::ToStart => {
+                for row in 0..row {
+                    self.clear(row, 0..self.cols, &blank);
+                }
+                self.clear(row, 0..col + 1, &blank);
+            }
+            Erase::All => {
+                for row in 0..self.rows {
+                    self.clear(row, 0..self.cols, &blank);
+                }
+            }
+            Erase::Scrollback => {
+                self.history.clear();
+                self.display_offset = 0;
+            }
+        }
+    }
+
+    pub fn erase_line(&mut self, erase: Erase, blank: Cell) {
+        let Cursor { row, col } = self.cursor;
+        match erase {
+            Erase::ToEnd => self.clear(row, col..self.cols, &blank),
+            Erase::ToStart => self.clear(row, 0..col + 1, &blank),
+            Erase::All | Erase::Scrollback => self.clear(row, 0..self.cols, &blank),
+        }
+    }
+
+    pub fn erase_chars(&mut self, n: usize, blank: Cell) {
+        let Cursor { row, col } = self.cursor;
+        self.clear(row, col..col + n, &blank);
+    }
+
+    pub fn insert_chars(&mut self, n: usize, blank: Cell) {
+        let Cursor { row, col } = self.cursor;
+        let n = n.min(self.cols - col);
+        let line = &mut self.lines[row].cells;
+        line.truncate(self.cols - n);
+        line.splice(col..col, vec![blank; n]);
+    }
+
+    pub fn delete_chars(&mut self, n: usize, blank: Cell) {
+        let Cursor { row, col } = self.cursor;
+        let n = n.min(self.cols - col);
+        let line = &mut self.lines[row].cells;
+        line.drain(col..col + n);
+        line.resize(self.cols, blank);
+    }
+
+    /// Inserts blank lines at the cursor, pushing the rest of the region down
+    pub fn insert_lines(&mut self, n: usize, blank: Cell) {
+        let region = self.scroll_region.clone();
+        if !region.contains(&self.cursor.row) {
+            return;
+        }
+        let n = n.min(region.end - self.cursor.row);
+        self.lines.drain(region.end - n..region.end);
+        let new_lines = vec![Row::new(self.cols, blank); n];
+        self.lines.splice(self.cursor.row..self.cursor.row, new_lines);
+    }
+
+    pub fn delete_lines(&mut self, n: usize, blank: Cell) {
+        let region = self.scroll_region.clone();
+        if !region.contains(&self.cursor.row) {
+            return;
+        }
+        let n = n.min(region.end - self.cursor.row);
+        self.lines.drain(self.cursor.row..self.cursor.row + n);
+        let new_lines = vec![Row::new(self.cols, blank); n];
+        self.lines.splice(region.end - n..region.end - n, new_lines);
+    }
+
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        let cols = cols.max(1);
+        let rows = rows.max(1);
+
+        // Keep the cursor on the screen by pushing lines above it into history
+        if self.cursor.row >= rows {
+            let shift = self.cursor.row + 1 - rows;
+            self.scroll_region = 0..self.rows;
+            self.scroll_up(shift, Cell::default());
+            self.cursor.row -= shift;
+        }
+        self.lines.resize(rows, Row::new(cols, Cell::default()));
+        // Scrolled back rows are drawn in the same columns as the screen
+        for line in self.lines.iter_mut().chain(self.history.iter_mut()) {
+            line.cells.resize(cols, Cell::default());
+        }
+
+        self.cols = cols;
+        self.rows = rows;
+        self.scroll_region = 0..rows;
+        self.cursor.col = self.cursor.col.min(cols - 1);
+        self.wrap_pending = false;
+    }
+
+    fn push_history(&mut self, rows: Vec<Row>) {
+        self.scrolled += rows.len();
+        // Keep the view on the same lines while it is scrolled back
+        if self.display_offset > 0 {
+            self.display_offset += rows.len();
+        }
+        self.history.extend(rows);
+        if self.history.len() > self.scrollback {
+            self.history.drain(..self.history.len() - self.scrollback);
+        }
+        self.display_offset = self.display_offset.min(self.history.len());
+    }
+
+    fn clear(&mut self, row: usize, cols: Range<usize>, blank: &Cell) {
+        let end = cols.end.min(self.cols);
+        let line = &mut self.lines[row];
+        line.cells[cols.start.min(end)..end].fill(blank.clone());
+        if end == self.cols {
+            line.wrapped = false;
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use crate::shell::Modifiers;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Button {
+    Left,
+    Middle,
+    Right,
+}
+
+impl Button {
+    fn code(self) -> u8 {
+        match self {
+            Button::Left => 0,
+            Button::Middle => 1,
+            Button::Right => 2,
+        }
+    }
+}
+
+/// Mouse events a program can ask to be told about
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MouseEvent {
+    Press(Button),
+    Release(Button),
+    /// The mouse moved to another cell, with the button held if there is one
+    Motion(Option<Button>),
+    WheelUp,
+    WheelDown,
+}
+
+/// Which mouse events are reported, set by modes 1000, 1002 and 1003
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Tracking {
+    #[default]
+    Off,
+    /// Presses, releases and the wheel
+    Click,
+    /// Motion while a button is held as well
+    Drag,
+    /// Any motion as well
+    Motion,
+}
+
+/// How reported mouse events are written, set by modes 1006 and 1015
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// `ESC[M` followed by three bytes offset by 32, cells past 223 cannot be reported
+    #[default]
+    X10,
+    /// `ESC[<b;x;yM`, or `m` for a release
+    Sgr,
+    /// `ESC[b;x;yM` with the button offset by 32 like X10
+    Urxvt,
+}
+
+/// Bytes telling the program about `event` at `col`, `row` of the screen, `None` if the
+/// tracking mode leaves it out or the position cannot be encoded
+pub fn report(
+    event: MouseEvent,
+    col: usize,
+    row: usize,
+    modifiers: Modifiers,
+    tracking: Tracking,
+    encoding: Encoding,
+) -> Option<Vec<u8>> {
+    let reported = match event {
+        MouseEvent::Motion(None) => tracking == Tracking::Motion,
+        MouseEvent::Motion(Some(_)) => matches!(tracking, Tracking::Drag | Tracking::Motion),
+        _ => tracking != Tracking::Off,
+    };
+    if !reported {
+        return None;
+    }
+
+    let button = match event {
+        MouseEvent::Press(button) => button.code(),
+        // Only SGR tells which button was released
+        MouseEvent::Release(button) if encoding == Encoding::Sgr => button.code(),
+        MouseEvent::Release(_) => 3,
+        MouseEvent::Motion(button) => 32 + button.map_or(3, Button::code),
+        MouseEvent::WheelUp => 64,
+        MouseEvent::WheelDown => 65,
+    };
+    let button = button
+        + if modifiers.shift { 4 } else { 0 }
+        + if modifiers.alt { 8 } else { 0 }
+        + if modifiers.ctrl { 16 } else { 0 };
+    let (x, y) = (col + 1, row + 1);
+
+    match encoding {
+        Encoding::X10 => {
+            let x = u8::try_from(x + 32).ok()?;
+            let y = u8::try_from(y + 32).ok()?;
+            Some(vec![0x1b, b'[', b'M', button + 32, x, y])
+        }
+        Encoding::Sgr => {
+            let end = if matches!(event, MouseEvent::Release(_)) { 'm' } else { 'M' };
+            Some(format!("\x1b[<{};{};{}{}", button, x, y, end).into_bytes())
+        }
+        Encoding::Urxvt => Some(format!("\x1b[{};{};{}M", button + 32, x, y).into_bytes()),
+    }
+}
@@ -0,0 +1,752 @@
+//! Byte level parser for VT100/xterm control sequences, following the state machine
+//! described at https://vt100.net/emu/dec_ansi_parser
+
+const MAX_PARAMS: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Color {
+    /// One of the 16 base colors, 8..16 are the bright variants
+    Named(u8),
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// Single change of the graphic rendition (SGR)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Attr {
+    Reset,
+    Bold,
+    Dim,
+    Italic,
+    Underline,
+    Blink,
+    Inverse,
+    Hidden,
+    Strike,
+    /// Cancels both bold and dim
+    CancelBoldDim,
+    CancelItalic,
+    CancelUnderline,
+    CancelBlink,
+    CancelInverse,
+    CancelHidden,
+    CancelStrike,
+    Foreground(Color),
+    Background(Color),
+    DefaultForeground,
+    DefaultBackground,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// IRM
+    Insert,
+    /// LNM
+    LineFeedNewLine,
+    /// DECCKM
+    CursorKeys,
+    /// DECOM
+    Origin,
+    /// DECAWM
+    AutoWrap,
+    /// DECTCEM
+    ShowCursor,
+    /// Pasted text is sent between `ESC[200~` and `ESC[201~`
+    BracketedPaste,
+    /// Switches to the alternate screen, 47
+    AlternateScreen,
+    /// 1047, clears the alternate screen when leaving it
+    AlternateScreenClear,
+    /// 1049, saves the cursor and clears the alternate screen when entering it
+    AlternateScreenSaveCursor,
+    /// Reports presses and releases of mouse buttons, 1000
+    MouseClick,
+    /// 1002, reports motion while a button is held as well
+    MouseDrag,
+    /// 1003, reports any motion as well
+    MouseMotion,
+    /// 1006, mouse reports use the SGR encoding
+    SgrMouse,
+    /// 1015, mouse reports use the urxvt encoding
+    UrxvtMouse,
+    Unknown {
+        private: bool,
+        mode: u16,
+    },
+}
+
+impl Mode {
+    fn new(private: bool, mode: u16) -> Mode {
+        match (private, mode) {
+            (false, 4) => Mode::Insert,
+            (false, 20) => Mode::LineFeedNewLine,
+            (true, 1) => Mode::CursorKeys,
+            (true, 6) => Mode::Origin,
+            (true, 7) => Mode::AutoWrap,
+            (true, 25) => Mode::ShowCursor,
+            (true, 47) => Mode::AlternateScreen,
+            (true, 1047) => Mode::AlternateScreenClear,
+            (true, 1049) => Mode::AlternateScreenSaveCursor,
+            (true, 1000) => Mode::MouseClick,
+            (true, 1002) => Mode::MouseDrag,
+            (true, 1003) => Mode::MouseMotion,
+            (true, 1006) => Mode::SgrMouse,
+            (true, 1015) => Mode::UrxvtMouse,
+            (true, 2004) => Mode::BracketedPaste,
+            (private, mode) => Mode::Unknown { private, mode },
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Erase {
+    ToEnd,
+    ToStart,
+    All,
+    /// Lines scrolled off the screen, only valid for erase in display
+    Scrollback,
+}
+
+/// What the terminal has to do in response to the parsed input.
+/// Rows and columns are zero based.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    Print(char),
+    Bell,
+    Backspace,
+    Tab,
+    LineFeed,
+    CarriageReturn,
+    CursorUp(usize),
+    CursorDown(usize),
+    CursorForward(usize),
+    CursorBackward(usize),
+    CursorNextLine(usize),
+    CursorPrecedingLine(usize),
+    CursorColumn(usize),
+    CursorRow(usize),
+    CursorPosition {
+        row: usize,
+        col: usize,
+    },
+    SaveCursor,
+    RestoreCursor,
+    Index,
+    ReverseIndex,
+    NextLine,
+    EraseInDisplay(Erase),
+    EraseInLine(Erase),
+    EraseChars(usize),
+    InsertChars(usize),
+    DeleteChars(usize),
+    InsertLines(usize),
+    DeleteLines(usize),
+    ScrollUp(usize),
+    ScrollDown(usize),
+    /// `bottom` is exclusive, `None` means the last row of the screen
+    SetScrollRegion {
+        top: usize,
+        bottom: Option<usize>,
+    },
+    Sgr(Attr),
+    SetMode(Mode),
+    ResetMode(Mode),
+    DeviceStatus,
+    CursorPositionReport,
+    DeviceAttributes,
+    SetTitle(String),
+    Reset,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    EscapeIntermediate,
+    CsiEntry,
+    CsiParam,
+    CsiIntermediate,
+    CsiIgnore,
+    OscString,
+    DcsEntry,
+    DcsParam,
+    DcsIntermediate,
+    DcsPassthrough,
+    DcsIgnore,
+    SosPmApcString,
+}
+
+pub struct Parser {
+    state: State,
+    params: Vec<u16>,
+    param: Option<u16>,
+    intermediates: Vec<u8>,
+    private_marker: Option<u8>,
+    osc: Vec<u8>,
+    utf8: Vec<u8>,
+    actions: Vec<Action>,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            state: State::Ground,
+            params: vec![],
+            param: None,
+            intermediates: vec![],
+            private_marker: None,
+            osc: vec![],
+            utf8: vec![],
+            actions: vec![],
+        }
+    }
+
+    /// Feeds raw output of a program into the parser. Sequences split between calls
+    /// are kept until the rest of them arrives.
+    pub fn parse(&mut self, bytes: &[u8]) -> Vec<Action> {
+        for &byte in bytes {
+            self.advance(byte);
+        }
+        std::mem::take(&mut self.actions)
+    }
+
+    fn advance(&mut self, byte: u8) {
+        if !self.utf8.is_empty() {
+            self.advance_utf8(byte);
+            return;
+        }
+
+        // Transitions valid from any state
+        match byte {
+            0x18 | 0x1a => {
+                self.execute(byte);
+                self.state = State::Ground;
+                return;
+            }
+            0x1b => {
+                if self.state == State::OscString {
+                    self.osc_dispatch();
+                }
+                self.clear();
+                self.state = State::Escape;
+                return;
+            }
+            _ => (),
+        }
+
+        match self.state {
+            State::Ground => match byte {
+                0x00..=0x1f => self.execute(byte),
+                0x20..=0x7e => self.actions.push(Action::Print(byte as char)),
+                0x7f => (),
+                _ => self.advance_utf8(byte),
+            },
+            State::Escape => match byte {
+                0x00..=0x1f => self.execute(byte),
+                0x20..=0x2f => {
+                    self.intermediates.push(byte);
+                    self.state = State::EscapeIntermediate;
+                }
+                b'[' => self.state = State::CsiEntry,
+                b']' => self.state = State::OscString,
+                b'P' => self.state = State::DcsEntry,
+                b'X' | b'^' | b'_' => self.state = State::SosPmApcString,
+                0x30..=0x7e => {
+                    self.esc_dispatch(byte);
+                    self.state = State::Ground;
+                }
+                _ => (),
+            },
+            State::EscapeIntermediate => match byte {
+                0x00..=0x1f => self.execute(byte),
+                0x20..=0x2f => self.intermediates.push(byte),
+                0x30..=0x7e => {
+                    self.esc_dispatch(byte);
+                    self.state = State::Ground;
+                }
+                _ => (),
+            },
+            State::CsiEntry => match byte {
+                0x00..=0x1f => self.execute(byte),
+                0x20..=0x2f => {
+                    self.intermediates.push(byte);
+                    self.state = State::CsiIntermediate;
+                }
+                0x30..=0x3b => {
+                    self.param(byte);
+                    self.state = State::CsiParam;
+                }
+                0x3c..=0x3f => {
+                    self.private_marker = Some(byte);
+                    self.state = State::CsiParam;
+                }
+                0x40..=0x7e => {
+                    self.csi_dispatch(byte);
+                    self.state = State::Ground;
+                }
+                _ => (),
+            },
+            State::CsiParam => match byte {
+                0x00..=0x1f => self.execute(byte),
+                0x20..=0x2f => {
+                    self.intermediates.push(byte);
+                    self.state = State::CsiIntermediate;
+                }
+                0x30..=0x3b => self.param(byte),
+                0x3c..=0x3f => self.state = State::CsiIgnore,
+                0x40..=0x7e => {
+                    self.csi_dispatch(byte);
+                    self.state = State::Ground;
+                }
+                _ => (),
+            },
+            State::CsiIntermediate => match byte {
+                0x00..=0x1f => self.execute(byte),
+                0x20..=0x2f => self.intermediates.push(byte),
+                0x30..=0x3f => self.state = State::CsiIgnore,
+                0x40..=0x7e => {
+                    self.csi_dispatch(byte);
+                    self.state = State::Ground;
+                }
+                _ => (),
+            },
+            State::CsiIgnore => match byte {
+                0x00..=0x1f => self.execute(byte),
+                0x40..=0x7e => self.state = State::Ground,
+                _ => (),
+            },
+            State::OscString => match byte {
+                0x07 => {
+                    self.osc_dispatch();
+                    self.state = State::Ground;
+                }
+                0x00..=0x1f => (),
+                _ => self.osc.push(byte),
+            },
+            State::DcsEntry | State::DcsParam | State::DcsIntermediate => match byte {
+                0x00..=0x1f | 0x7f => (),
+                0x20..=0x2f => self.state = State::DcsIntermediate,
+                0x30..=0x3f if self.state == State::DcsIntermediate => {
+                    self.state = State::DcsIgnore
+                }
+                0x30..=0x3f => self.state = State::DcsParam,
+                // None of the device control strings are supported, so their data is dropped
+                _ => self.state = State::DcsPassthrough,
+            },
+            State::DcsPassthrough | State::DcsIgnore | State::SosPmApcString => (),
+        }
+    }
+
+    fn advance_utf8(&mut self, byte: u8) {
+        if self.utf8.is_empty() {
+            match byte {
+                0xc2..=0xf4 => self.utf8.push(byte),
+                _ => self.actions.push(Action::Print(char::REPLACEMENT_CHARACTER)),
+            }
+            return;
+        }
+
+        if !(0x80..=0xbf).contains(&byte) {
+            // Broken sequence, the byte starts something new
+            self.utf8.clear();
+            self.actions.push(Action::Print(char::REPLACEMENT_CHARACTER));
+            self.advance(byte);
+            return;
+        }
+
+        self.utf8.push(byte);
+        let len = match self.utf8[0] {
+            0xc2..=0xdf => 2,
+            0xe0..=0xef => 3,
+            _ => 4,
+        };
+        if self.utf8.len() == len {
+            let c = std::str::from_utf8(&self.utf8)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+            self.utf8.clear();
+            self.actions.push(Action::Print(c));
+        }
+    }
+
+    fn clear(&mut self) {
+        self.params.clear();
+        self.param = None;
+        self.intermediates.clear();
+        self.private_marker = None;
+        self.osc.clear();
+    }
+
+    fn param(&mut self, byte: u8) {
+        match byte {
+            // Subparameters like in `38:2:r:g:b` are treated as ordinary ones
+            b';' | b':' => {
+                if self.params.len() < MAX_PARAMS {
+                    self.params.push(self.param.take().unwrap_or(0));
+                }
+            }
+            _ => {
+                let digit = (byte - b'0') as u16;
+                self.param = Some(self.param.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            }
+        }
+    }
+
+    fn execute(&mut self, byte: u8) {
+        let action = match byte {
+            0x07 => Action::Bell,
+            0x08 => Action::Backspace,
+            0x09 => Action::Tab,
+            0x0a..=0x0c => Action::LineFeed,
+            0x0d => Action::CarriageReturn,
+            _ => return,
+        };
+        self.actions.push(action);
+    }
+
+    fn esc_dispatch(&mut self, byte: u8) {
+        if !self.intermediates.is_empty() {
+            // Character set designations and DEC tests
+            return;
+        }
+        let action = match byte {
+            b'7' => Action::SaveCursor,
+            b'8' => Action::RestoreCursor,
+            b'D' => Action::Index,
+            b'E' => Action::NextLine,
+            b'M' => Action::ReverseIndex,
+            b'c' => Action::Reset,
+            _ => return,
+        };
+        self.actions.push(action);
+    }
+
+    fn osc_dispatch(&mut self) {
+        let osc = String::from_utf8_lossy(&self.osc).to_string();
+        if let Some((command, text)) = osc.split_once(';') {
+            if command == "0" || command == "2" {
+                self.actions.push(Action::SetTitle(text.to_string()));
+            }
+        }
+        self.osc.clear();
+    }
+
+    fn csi_dispatch(&mut self, byte: u8) {
+        if let Some(param) = self.param.take() {
+            self.params.push(param);
+        } else if !self.params.is_empty() {
+            // Trailing separator
+            self.params.push(0);
+        }
+        let params = std::mem::take(&mut self.params);
+        // Missing or zero parameter means the default value
+        let arg = |i: usize, default: usize| match params.get(i) {
+            Some(&p) if p != 0 => p as usize,
+            _ => default,
+        };
+
+        let action = match (self.private_marker, self.intermediates.as_slice(), byte) {
+            (None, [], b'@') => Action::InsertChars(arg(0, 1)),
+            (None, [], b'A') => Action::CursorUp(arg(0, 1)),
+            (None, [], b'B' | b'e') => Action::CursorDown(arg(0, 1)),
+            (None, [], b'C' | b'a') => Action::CursorForward(arg(0, 1)),
+            (None, [], b'D') => Action::CursorBackward(arg(0, 1)),
+            (None, [], b'E') => Action::CursorNextLine(arg(0, 1)),
+            (None, [], b'F') => Action::CursorPrecedingLine(arg(0, 1)),
+            (None, [], b'G' | b'`') => Action::CursorColumn(arg(0, 1) - 1),
+            (None, [], b'H' | b'f') => {
+                Action::CursorPosition { row: arg(0, 1) - 1, col: arg(1, 1) - 1 }
+            }
+            (None, [], b'J') => match erase(&params) {
+                Some(erase) => Action::EraseInDisplay(erase),
+                None => return,
+            },
+            (None, [], b'K') => match erase(&params) {
+                Some(Erase::Scrollback) | None => return,
+                Some(erase) => Action::EraseInLine(erase),
+            },
+            (None, [], b'L') => Action::InsertLines(arg(0, 1)),
+            (None, [], b'M') => Action::DeleteLines(arg(0, 1)),
+            (None, [], b'P') => Action::DeleteChars(arg(0, 1)),
+            (None, [], b'S') => Action::ScrollUp(arg(0, 1)),
+            (None, [], b'T') => Action::ScrollDown(arg(0, 1)),
+            (None, [], b'X') => Action::EraseChars(arg(0, 1)),
+            (None, [], b'c') if arg(0, 0) == 0 => Action::DeviceAttributes,
+            (None, [], b'd') => Action::CursorRow(arg(0, 1) - 1),
+            (None, [], b'm') => {
+                self.actions.extend(sgr(&params).into_iter().map(Action::Sgr));
+                return;
+            }
+            (None, [], b'n') => match arg(0, 0) {
+                5 => Action::DeviceStatus,
+                6 => Action::CursorPositionReport,
+                _ => return,
+            },
+            (None, [], b'r') => Action::SetScrollRegion {
+                top: arg(0, 1) - 1,
+                bottom: params.get(1).filter(|&&p| p != 0).map(|&p| p as usize),
+            },
+            (None, [], b's') if params.is_empty() => Action::SaveCursor,
+            (None, [], b'u') => Action::RestoreCursor,
+            (marker @ (None | Some(b'?')), [], b'h' | b'l') => {
+                let private = marker.is_some();
+                for &mode in &params {
+                    let mode = Mode::new(private, mode);
+                    self.actions.push(match byte {
+                        b'h' => Action::SetMode(mode),
+                        _ => Action::ResetMode(mode),
+                    });
+                }
+                return;
+            }
+            _ => return,
+        };
+        self.actions.push(action);
+    }
+}
+
+fn erase(params: &[u16]) -> Option<Erase> {
+    match params.first().unwrap_or(&0) {
+        0 => Some(Erase::ToEnd),
+        1 => Some(Erase::ToStart),
+        2 => Some(Erase::All),
+        3 => Some(Erase::Scrollback),
+        _ => None,
+    }
+}
+
+fn sgr(params: &[u16]) -> Vec<Attr> {
+    if params.is_empty() {
+        return vec![Attr::Reset];
+    }
+
+    let mut attrs = vec![];
+    let mut params = params.iter().copied();
+    while let Some(param) = params.next() {
+        let attr = match param {
+            0 => Attr::Reset,
+            1 => Attr::Bold,
+            2 => Attr::Dim,
+            3 => Attr::Italic,
+            4 | 21 => Attr::Underline,
+            5 | 6 => Attr::Blink,
+            7 => Attr::Inverse,
+            8 => Attr::Hidden,
+            9 => Attr::Strike,
+            22 => Attr::CancelBoldDim,
+            23 => Attr::CancelItalic,
+            24 => Attr::CancelUnderline,
+            25 => Attr::CancelBlink,
+            27 => Attr::CancelInverse,
+            28 => Attr::CancelHidden,
+            29 => Attr::CancelStrike,
+            30..=37 => Attr::Foreground(Color::Named((param - 30) as u8)),
+            38 => match extended_color(&mut params) {
+                Some(color) => Attr::Foreground(color),
+                None => break,
+            },
+            39 => Attr::DefaultForeground,
+            40..=47 => Attr::Background(Color::Named((param - 40) as u8)),
+            48 => match extended_color(&mut params) {
+                Some(color) => Attr::Background(color),
+                None => break,
+            },
+            49 => Attr::DefaultBackground,
+            90..=97 => Attr::Foreground(Color::Named((param - 90 + 8) as u8)),
+            100..=107 => Attr::Background(Color::Named((param - 100 + 8) as u8)),
+            _ => continue,
+        };
+        attrs.push(attr);
+    }
+    attrs
+}
+
+/// Parses the rest of `38;5;n` and `38;2;r;g;b`
+fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    match params.next()? {
+        5 => Some(Color::Indexed(params.next()? as u8)),
+        2 => {
+            let r = params.next()? as u8;
+            let g = params.next()? as u8;
+            let b = params.next()? as u8;
+            Some(Color::Rgb(r, g, b))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Vec<Action> {
+        Parser::new().parse(bytes)
+    }
+
+    #[test]
+    fn prints_text_and_executes_controls() {
+        assert_eq!(
+            parse(b"ab\r\n\x08\t\x07"),
+            vec![
+                Action::Print('a'),
+                Action::Print('b'),
+                Action::CarriageReturn,
+                Action::LineFeed,
+                Action::Backspace,
+                Action::Tab,
+                Action::Bell,
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_utf8_split_between_reads() {
+        let mut parser = Parser::new();
+        let bytes = "é😀".as_bytes();
+        let mut actions = parser.parse(&bytes[..1]);
+        assert!(actions.is_empty());
+        actions.extend(parser.parse(&bytes[1..4]));
+        actions.extend(parser.parse(&bytes[4..]));
+        assert_eq!(actions, vec![Action::Print('é'), Action::Print('😀')]);
+    }
+
+    #[test]
+    fn replaces_invalid_utf8() {
+        assert_eq!(
+            parse(b"\xffa\xc3b"),
+            vec![
+                Action::Print(char::REPLACEMENT_CHARACTER),
+                Action::Print('a'),
+                Action::Print(char::REPLACEMENT_CHARACTER),
+                Action::Print('b'),
+            ]
+        );
+    }
+
+    #[test]
+    fn cursor_movement() {
+        assert_eq!(
+            parse(b"\x1b[A\x1b[3B\x1b[0C\x1b[2D\x1b[5;10H\x1b[H\x1b[7G\x1b[4d"),
+            vec![
+                Action::CursorUp(1),
+                Action::CursorDown(3),
+                Action::CursorForward(1),
+                Action::CursorBackward(2),
+                Action::CursorPosition { row: 4, col: 9 },
+                Action::CursorPosition { row: 0, col: 0 },
+                Action::CursorColumn(6),
+                Action::CursorRow(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn erase() {
+        assert_eq!(
+            parse(b"\x1b[J\x1b[1J\x1b[2J\x1b[3J\x1b[K\x1b[1K\x1b[2K\x1b[4X"),
+            vec![
+                Action::EraseInDisplay(Erase::ToEnd),
+                Action::EraseInDisplay(Erase::ToStart),
+                Action::EraseInDisplay(Erase::All),
+                Action::EraseInDisplay(Erase::Scrollback),
+                Action::EraseInLine(Erase::ToEnd),
+                Action::EraseInLine(Erase::ToStart),
+                Action::EraseInLine(Erase::All),
+                Action::EraseChars(4),
+            ]
+        );
+    }
+
+    #[test]
+    fn sgr() {
+        assert_eq!(
+            parse(b"\x1b[m\x1b[1;4;31;102m\x1b[38;5;208;48;2;1;2;3m\x1b[38:2:10:20:30m\x1b[22;39m"),
+            vec![
+                Action::Sgr(Attr::Reset),
+                Action::Sgr(Attr::Bold),
+                Action::Sgr(Attr::Underline),
+                Action::Sgr(Attr::Foreground(Color::Named(1))),
+                Action::Sgr(Attr::Background(Color::Named(10))),
+                Action::Sgr(Attr::Foreground(Color::Indexed(208))),
+                Action::Sgr(Attr::Background(Color::Rgb(1, 2, 3))),
+                Action::Sgr(Attr::Foreground(Color::Rgb(10, 20, 30))),
+                Action::Sgr(Attr::CancelBoldDim),
+                Action::Sgr(Attr::DefaultForeground),
+            ]
+        );
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!(
+            parse(b"\x1b[?1;25h\x1b[4l\x1b[?7l\x1b[?2004h\x1b[?1049l\x1b[?12345h"),
+            vec![
+                Action::SetMode(Mode::CursorKeys),
+                Action::SetMode(Mode::ShowCursor),
+                Action::ResetMode(Mode::Insert),
+                Action::ResetMode(Mode::AutoWrap),
+                Action::SetMode(Mode::BracketedPaste),
+                Action::ResetMode(Mode::AlternateScreenSaveCursor),
+                Action::SetMode(Mode::Unknown { private: true, mode: 12345 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn osc_title_with_bel_and_st() {
+        assert_eq!(
+            parse(b"\x1b]0;user@host: ~\x07\x1b]2;vim\x1b\\x"),
+            vec![
+                Action::SetTitle("user@host: ~".to_string()),
+                Action::SetTitle("vim".to_string()),
+                Action::Print('x'),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_unsupported_strings_and_sequences() {
+        assert_eq!(
+            parse(b"\x1bP1$r0m\x1b\\\x1b_apc\x1b\\\x1b[>4;2m\x1b(Ba\x1b[?1$p"),
+            vec![Action::Print('a')]
+        );
+    }
+
+    #[test]
+    fn cancel_aborts_sequence() {
+        assert_eq!(parse(b"\x1b[1\x18a"), vec![Action::Print('a')]);
+    }
+
+    #[test]
+    fn escape_sequences() {
+        assert_eq!(
+            parse(b"\x1b7\x1b8\x1bD\x1bE\x1bM\x1bc"),
+            vec![
+                Action::SaveCursor,
+                Action::RestoreCursor,
+                Action::Index,
+                Action::NextLine,
+                Action::ReverseIndex,
+                Action::Reset,
+            ]
+        );
+    }
+
+    #[test]
+    fn scroll_region() {
+        assert_eq!(
+            parse(b"\x1b[2;20r\x1b[r"),
+            vec![
+                Action::SetScrollRegion { top: 1, bottom: Some(20) },
+                Action::SetScrollRegion { top: 0, bottom: None },
+            ]
+        );
+    }
+}
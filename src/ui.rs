@@ -1,12 +1,28 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use crate::{graphics::cursor::Cursor, resources::Resources, shell::Event};
+use crate::{
+    config::{self, Config},
+    graphics::cursor::Cursor,
+    resources::Resources,
+    shell::Event,
+    terminal::{
+        grid::{Flags, Grid},
+        mouse::Button,
+    },
+};
 
-use self::textdisplay::{Buffer, TextDisplay};
+use self::{
+    palette::Palette,
+    selection::{Kind, Point, Selection},
+    textdisplay::TextDisplay,
+};
 
+pub mod palette;
+pub mod selection;
 pub mod textdisplay;
 
-const GREEN: (u8, u8, u8, u8) = (0, 227, 48, 255);
+/// Presses on the same cell closer together than this make a double or triple click
+const MULTI_CLICK: Duration = Duration::from_millis(400);
 
 // pub enum Event {
 //     WindowEvent,
@@ -21,12 +37,41 @@ enum CursorState {
 }
 
 pub struct Ui {
+    gl: gl::Gl,
+    res: Resources,
+    width: u32,
+    height: u32,
     textdisplay: TextDisplay,
     cursor: Cursor,
-    cursor_position: usize,
+    cursor_visible: bool,
+    /// The cursor is off the view while it is scrolled back
+    cursor_in_view: bool,
     cursor_state: CursorState,
+    blink: config::Cursor,
 
     last_press: Instant,
+    selection: Option<Selection>,
+    /// Time and cell of the last press and how many clicks it made in a row
+    last_click: Option<(Instant, Point)>,
+    clicks: usize,
+}
+
+/// What a session draws its terminal into and takes window events from. `Ui` draws
+/// with OpenGL, tests of the built-in shell use a stand-in without a window
+pub trait View {
+    /// Applies a reloaded config, or shows why it could not be loaded and keeps the
+    /// current settings. The caller redraws the text afterwards, the size in cells may
+    /// have changed
+    fn reload_config(&mut self, config: Result<&Config, &str>);
+    fn handle_event(&mut self, event: Event);
+    /// Selects text with the mouse, returns whether the selection changed
+    fn handle_mouse(&mut self, event: Event, grid: &Grid) -> bool;
+    fn selection_text(&self, grid: &Grid) -> Option<String>;
+    fn update_text(&mut self, grid: &Grid);
+    fn update_cursor(&mut self, grid: &Grid);
+    fn render(&mut self);
+    /// Columns and rows of text that fit into the window
+    fn size_in_cells(&self) -> (usize, usize);
 }
 
 impl Ui {
@@ -35,45 +80,161 @@ impl Ui {
         gl: &gl::Gl,
         width: u32,
         height: u32,
+        config: &Config,
     ) -> Result<Self, anyhow::Error> {
-        let color = GREEN;
-        let textdisplay = TextDisplay::new(res, gl, width, height, color)?;
+        let (textdisplay, cursor) = Ui::build(res, gl, width, height, config)?;
+
+        anyhow::Ok(Ui {
+            gl: gl.clone(),
+            res: res.clone(),
+            width,
+            height,
+            textdisplay,
+            cursor,
+            cursor_visible: true,
+            cursor_in_view: true,
+            cursor_state: CursorState::Blinking,
+            blink: config.cursor.clone(),
+            last_press: Instant::now(),
+            selection: None,
+            last_click: None,
+            clicks: 0,
+        })
+    }
+
+    fn build(
+        res: &Resources,
+        gl: &gl::Gl,
+        width: u32,
+        height: u32,
+        config: &Config,
+    ) -> Result<(TextDisplay, Cursor), anyhow::Error> {
+        let textdisplay = TextDisplay::new(res, gl, width, height, config)?;
         let cursor = Cursor::new(
             res,
             gl,
-            textdisplay.line_height as f32 * 0.6,
+            textdisplay.glyph_width as f32,
             textdisplay.line_height as f32,
             width as f32,
             height as f32,
-            color,
+            config.window.padding as f32,
+            config.cursor.style,
+            Palette::new(&config.colors).foreground.rgba(),
         )?;
+        anyhow::Ok((textdisplay, cursor))
+    }
 
-        anyhow::Ok(Ui {
-            textdisplay,
-            cursor,
-            cursor_position: 0,
-            cursor_state: CursorState::Blinking,
-            last_press: Instant::now(),
-        })
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+    }
+
+    /// Cell of the view under a position in pixels
+    fn point_at(&self, x: f32, y: f32, grid: &Grid) -> Point {
+        let (col, row) = self.cell_at(x, y, grid);
+        Point { line: grid.visible_to_absolute(row), col }
+    }
+
+    /// Column and row of the view under a position in pixels, from the size of the glyphs.
+    /// Positions outside the text are clamped to the nearest cell
+    pub fn cell_at(&self, x: f32, y: f32, grid: &Grid) -> (usize, usize) {
+        let padding = self.textdisplay.padding as f32;
+        let col = ((x - padding) / self.textdisplay.glyph_width as f32).max(0.0) as usize;
+        let row = ((y - padding) / self.textdisplay.line_height as f32).max(0.0) as usize;
+        (col.min(grid.cols() - 1), row.min(grid.rows() - 1))
+    }
+
+    fn update_size(&mut self, width: i32, height: i32) {
+        self.width = width as u32;
+        self.height = height as u32;
+        self.textdisplay.update_size(width, height);
+        self.cursor.update_size(width as f32, height as f32);
     }
+}
 
-    pub fn handle_event(&mut self, event: Event) {
+impl View for Ui {
+    fn reload_config(&mut self, config: Result<&Config, &str>) {
+        let result = config.map_err(str::to_string).and_then(|config| {
+            let (textdisplay, cursor) =
+                Ui::build(&self.res, &self.gl, self.width, self.height, config)
+                    .map_err(|e| format!("{:#}", e))?;
+            self.textdisplay = textdisplay;
+            self.cursor = cursor;
+            self.blink = config.cursor.clone();
+            std::result::Result::Ok(())
+        });
+        let message = result.err().map(|e| format!("Config not applied: {}", e));
+        self.textdisplay.set_message(message);
+    }
+
+    fn handle_event(&mut self, event: Event) {
         match event {
             Event::Resized(width, height) => {
                 self.update_size(width as i32, height as i32);
             }
             Event::Release => self.cursor_state = CursorState::TriggeredBlinking,
+            event if event.is_mouse() => (),
             _ => self.cursor_state = CursorState::Visible,
         }
+        // Typing changes the text under the selection
+        if event.is_input() {
+            self.selection = None;
+        }
+    }
+
+    /// Selects text with the left button, a double click selects words and a triple click
+    /// lines. Dragging with Alt held selects a block. Returns whether the selection changed
+    fn handle_mouse(&mut self, event: Event, grid: &Grid) -> bool {
+        match event {
+            Event::MouseDown { button: Button::Left, x, y, modifiers } => {
+                let point = self.point_at(x, y, grid);
+                let now = Instant::now();
+                self.clicks = match self.last_click {
+                    Some((time, last)) if last == point && now - time < MULTI_CLICK => {
+                        self.clicks % 3 + 1
+                    }
+                    _ => 1,
+                };
+                self.last_click = Some((now, point));
+                let kind = match self.clicks {
+                    _ if modifiers.alt => Kind::Block,
+                    1 => Kind::Simple,
+                    2 => Kind::Word,
+                    _ => Kind::Line,
+                };
+                self.selection = Some(Selection::new(kind, point));
+                true
+            }
+            Event::MouseMove { button: Some(Button::Left), x, y, .. } => {
+                let point = self.point_at(x, y, grid);
+                match &mut self.selection {
+                    Some(selection) => {
+                        selection.extend(point);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        }
     }
 
-    pub fn update_text(&mut self, buffer: &Buffer) {
-        self.textdisplay.update(buffer);
+    fn selection_text(&self, grid: &Grid) -> Option<String> {
+        let range = self.selection.as_ref()?.range(grid)?;
+        Some(range.text(grid))
     }
 
-    pub fn render(&mut self) {
+    fn update_text(&mut self, grid: &Grid) {
+        let selection = self.selection.as_ref().and_then(|selection| selection.range(grid));
+        self.textdisplay.update(grid, selection.as_ref());
+    }
+
+    fn render(&mut self) {
         self.textdisplay.render();
 
+        if !self.cursor_visible || !self.cursor_in_view {
+            return;
+        }
+
         match self.cursor_state {
             CursorState::Visible => {
                 self.last_press = Instant::now();
@@ -81,15 +242,15 @@ impl Ui {
             }
             CursorState::Blinking => {
                 let duration = self.last_press.elapsed();
-                if duration.as_millis() < 400 {
+                if duration < Duration::from_millis(self.blink.blink_visible) {
                     self.cursor.render();
-                } else if duration.as_millis() > 1000 {
+                } else if duration > Duration::from_millis(self.blink.blink_period) {
                     self.last_press = Instant::now();
                 }
             }
             CursorState::TriggeredBlinking => {
                 let duration = self.last_press.elapsed();
-                if duration.as_millis() > 500 {
+                if duration > Duration::from_millis(self.blink.blink_after_typing) {
                     self.cursor_state = CursorState::Blinking;
                 }
                 self.cursor.render()
@@ -97,61 +258,16 @@ impl Ui {
         }
     }
 
-    pub fn shift_cursor(&mut self, shift: i32, input_size: usize) {
-        if input_size == 0 {
-            self.cursor_position = 0;
-            return;
-        }
-        let cursor_position = self.cursor_position as i32 + shift;
-        if cursor_position > input_size as i32 {
-            self.cursor_position = input_size;
-        } else if cursor_position >= 0 {
-            self.cursor_position = cursor_position as usize;
-        }
-        // dbg!(input_size);
-        // dbg!(cursor_position);
-    }
-
-    pub fn update_cursor(&mut self, history: &[String]) {
-        let line_width = self.textdisplay.get_line_width();
-
-        let history_last_line_width = if let Some(history_last_line) = history.last() {
-            history_last_line.len() % line_width
-        } else {
-            0
-        };
-
-        let mut history_last_line_y = history.iter().fold(history.len() - 1, |acc, s| {
-            // let line_length = s.len();
-            // let line_width = line_width as usize;
-            // dbg!(acc);
-            // dbg!(line_length);
-            // dbg!(line_width);
-            // dbg!((line_length - 1.) / (line_width));
-            acc + ((s.len().wrapping_sub(1)) / (line_width as usize))
-        }); // Adding remainder of each row if it's longer than line width
-
-        // dbg!(self.textdisplay.lines_to_display);
-        if history_last_line_y > self.textdisplay.lines_to_display - 1 {
-            history_last_line_y = self.textdisplay.lines_to_display - 1
-        }
-        let last_line_width = history_last_line_width + self.cursor_position;
-        let new_x = last_line_width % line_width;
-        let new_y = history_last_line_y + (last_line_width) / line_width;
-
-        // dbg!(self.cursor_position);
-        // dbg!(line_width);
-        // dbg!(history_last_line_width);
-        // dbg!(history_last_line_y);
-        // dbg!(last_line_width);
-        // dbg!(new_x);
-        // dbg!(new_y);
-
-        self.cursor.move_to(new_x as u32, new_y as u32);
+    fn update_cursor(&mut self, grid: &Grid) {
+        let cursor = grid.cursor();
+        let row = cursor.row + grid.display_offset();
+        self.cursor_in_view = row < grid.rows();
+        let cell = &grid.row(cursor.row).cells[cursor.col];
+        let columns = if cell.flags.contains(Flags::WIDE) { 2 } else { 1 };
+        self.cursor.move_to(cursor.col as u32, row as u32, columns);
     }
 
-    fn update_size(&mut self, width: i32, height: i32) {
-        self.textdisplay.update_size(width, height);
-        self.cursor.update_size(width as f32, height as f32);
+    fn size_in_cells(&self) -> (usize, usize) {
+        (self.textdisplay.get_line_width(), self.textdisplay.get_lines_to_display())
     }
 }
@@ -0,0 +1,184 @@
+use crate::{
+    config::{self, Rgb, Scheme},
+    terminal::{
+        grid::{Cell, Flags},
+        parser::Color,
+    },
+};
+
+/// Foreground, background and the 16 base colors of a scheme
+struct SchemeColors {
+    foreground: &'static str,
+    background: &'static str,
+    base: [&'static str; 16],
+}
+
+const GREEN_PHOSPHOR: SchemeColors = SchemeColors {
+    foreground: "#00e330",
+    background: "#000000",
+    base: [
+        "#000000", "#00802b", "#00e330", "#66ff66", "#005c1f", "#00a336", "#33cc66", "#00e330",
+        "#004d1a", "#00b33c", "#33ff66", "#99ff99", "#008a2e", "#00d147", "#66ff99", "#b3ffc6",
+    ],
+};
+
+const AMBER: SchemeColors = SchemeColors {
+    foreground: "#ffb000",
+    background: "#000000",
+    base: [
+        "#000000", "#995c00", "#cc8400", "#ffb000", "#664000", "#b36b00", "#e69e00", "#ffb000",
+        "#4d3000", "#cc7a00", "#ffa31a", "#ffc94d", "#805000", "#e68a00", "#ffbf33", "#ffd780",
+    ],
+};
+
+/// Solarized maps its accents to the base colors and its greys to the bright ones
+const SOLARIZED: [&str; 16] = [
+    "#073642", "#dc322f", "#859900", "#b58900", "#268bd2", "#d33682", "#2aa198", "#eee8d5",
+    "#002b36", "#cb4b16", "#586e75", "#657b83", "#839496", "#6c71c4", "#93a1a1", "#fdf6e3",
+];
+
+const SOLARIZED_DARK: SchemeColors =
+    SchemeColors { foreground: "#839496", background: "#002b36", base: SOLARIZED };
+
+const SOLARIZED_LIGHT: SchemeColors =
+    SchemeColors { foreground: "#657b83", background: "#fdf6e3", base: SOLARIZED };
+
+const XTERM: SchemeColors = SchemeColors {
+    foreground: "#e5e5e5",
+    background: "#000000",
+    base: [
+        "#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5",
+        "#7f7f7f", "#ff0000", "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff",
+    ],
+};
+
+/// Steps of each channel in the 6x6x6 color cube of xterm
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Colors cells are drawn with: the 16 base colors of the scheme, the xterm color cube
+/// and grey ramp for indices 16..256, and 24-bit colors as they are
+pub struct Palette {
+    pub foreground: Rgb,
+    pub background: Rgb,
+    colors: [Rgb; 256],
+}
+
+impl Palette {
+    pub fn new(config: &config::Colors) -> Palette {
+        let scheme = match config.scheme {
+            Scheme::GreenPhosphor => GREEN_PHOSPHOR,
+            Scheme::Amber => AMBER,
+            Scheme::SolarizedDark => SOLARIZED_DARK,
+            Scheme::SolarizedLight => SOLARIZED_LIGHT,
+            Scheme::Xterm => XTERM,
+        };
+        let parse = |hex: &str| Rgb::try_from(hex.to_string()).expect("scheme colors are valid");
+
+        let mut colors = [Rgb(0, 0, 0); 256];
+        for (i, color) in colors.iter_mut().enumerate() {
+            *color = match i {
+                0..=15 => config.palette.get(i).copied().unwrap_or_else(|| parse(scheme.base[i])),
+                16..=231 => {
+                    let i = i - 16;
+                    Rgb(CUBE_LEVELS[i / 36], CUBE_LEVELS[i / 6 % 6], CUBE_LEVELS[i % 6])
+                }
+                _ => {
+                    let level = 8 + 10 * (i - 232) as u8;
+                    Rgb(level, level, level)
+                }
+            };
+        }
+
+        Palette {
+            foreground: config.foreground.unwrap_or_else(|| parse(scheme.foreground)),
+            background: config.background.unwrap_or_else(|| parse(scheme.background)),
+            colors,
+        }
+    }
+
+    pub fn color(&self, color: Color) -> Rgb {
+        match color {
+            Color::Named(i) | Color::Indexed(i) => self.colors[i as usize],
+            Color::Rgb(r, g, b) => Rgb(r, g, b),
+        }
+    }
+
+    /// Foreground and background a cell is drawn with, `None` shows the window background
+    pub fn cell_colors(&self, cell: &Cell) -> (Rgb, Option<Rgb>) {
+        let mut fg = match cell.fg {
+            // Bold text in one of the first 8 colors is drawn in its bright variant
+            Some(Color::Named(i)) if i < 8 && cell.flags.contains(Flags::BOLD) => {
+                self.colors[i as usize + 8]
+            }
+            Some(color) => self.color(color),
+            None => self.foreground,
+        };
+        let mut bg = cell.bg.map(|color| self.color(color));
+
+        if cell.flags.contains(Flags::DIM) {
+            fg = fg.scale(0.66);
+        }
+        if cell.flags.contains(Flags::INVERSE) {
+            let inverse_fg = bg.unwrap_or(self.background);
+            bg = Some(fg);
+            fg = inverse_fg;
+        }
+        if cell.flags.contains(Flags::HIDDEN) {
+            fg = bg.unwrap_or(self.background);
+        }
+        (fg, bg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette(scheme: Scheme) -> Palette {
+        Palette::new(&config::Colors { scheme, ..config::Colors::default() })
+    }
+
+    #[test]
+    fn indexed_colors_follow_xterm() {
+        let palette = palette(Scheme::Xterm);
+        assert_eq!(palette.color(Color::Named(1)), Rgb(0xcd, 0, 0));
+        assert_eq!(palette.color(Color::Indexed(16)), Rgb(0, 0, 0));
+        assert_eq!(palette.color(Color::Indexed(196)), Rgb(255, 0, 0));
+        assert_eq!(palette.color(Color::Indexed(110)), Rgb(135, 175, 215));
+        assert_eq!(palette.color(Color::Indexed(232)), Rgb(8, 8, 8));
+        assert_eq!(palette.color(Color::Indexed(255)), Rgb(238, 238, 238));
+        assert_eq!(palette.color(Color::Rgb(1, 2, 3)), Rgb(1, 2, 3));
+    }
+
+    #[test]
+    fn config_replaces_scheme_colors() {
+        let config = config::Colors {
+            scheme: Scheme::SolarizedDark,
+            foreground: Some(Rgb(1, 1, 1)),
+            background: None,
+            palette: vec![Rgb(2, 2, 2)],
+        };
+        let palette = Palette::new(&config);
+        assert_eq!(palette.foreground, Rgb(1, 1, 1));
+        assert_eq!(palette.background, Rgb(0x00, 0x2b, 0x36));
+        assert_eq!(palette.color(Color::Named(0)), Rgb(2, 2, 2));
+        assert_eq!(palette.color(Color::Named(1)), Rgb(0xdc, 0x32, 0x2f));
+    }
+
+    #[test]
+    fn cell_attributes_change_colors() {
+        let palette = palette(Scheme::Xterm);
+        let red = Cell { fg: Some(Color::Named(1)), ..Cell::default() };
+        assert_eq!(palette.cell_colors(&red), (Rgb(0xcd, 0, 0), None));
+
+        let bold = Cell { flags: Flags::BOLD, ..red.clone() };
+        assert_eq!(palette.cell_colors(&bold), (Rgb(0xff, 0, 0), None));
+
+        let inverse = Cell { flags: Flags::INVERSE, ..Cell::default() };
+        assert_eq!(palette.cell_colors(&inverse), (palette.background, Some(palette.foreground)));
+
+        let hidden = Cell { bg: Some(Color::Named(4)), flags: Flags::HIDDEN, ..red };
+        let blue = palette.color(Color::Named(4));
+        assert_eq!(palette.cell_colors(&hidden), (blue, Some(blue)));
+    }
+}
@@ -0,0 +1,270 @@
+use crate::terminal::grid::{Cell, Flags, Grid};
+
+/// Characters that end a word selected by a double click besides whitespace
+const WORD_SEPARATORS: &str = ",│`|:\"'()[]{}<>";
+
+/// Cell of the screen or the history, `line` is an absolute line like `Grid::absolute_row`
+/// so a selection stays on its text while the view scrolls
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Point {
+    pub line: usize,
+    pub col: usize,
+}
+
+/// What a drag selects, picked by the number of clicks that started it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Simple,
+    Word,
+    Line,
+    /// Rectangle of cells between the corners, dragged with Alt held
+    Block,
+}
+
+/// Text selected with the mouse, from where the button was pressed to where it was dragged
+#[derive(Clone, Debug)]
+pub struct Selection {
+    kind: Kind,
+    anchor: Point,
+    end: Point,
+}
+
+/// First and last selected cells, both included, after expanding to words or lines.
+/// A block covers the columns from `start` to `end` on each of its lines
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SelectionRange {
+    pub start: Point,
+    pub end: Point,
+    pub block: bool,
+}
+
+impl Selection {
+    pub fn new(kind: Kind, point: Point) -> Selection {
+        Selection { kind, anchor: point, end: point }
+    }
+
+    pub fn extend(&mut self, point: Point) {
+        self.end = point;
+    }
+
+    /// Cells covered on `grid`, `None` while a simple selection has not been dragged
+    pub fn range(&self, grid: &Grid) -> Option<SelectionRange> {
+        if matches!(self.kind, Kind::Simple | Kind::Block) && self.anchor == self.end {
+            return None;
+        }
+        let (start, end) = (self.anchor.min(self.end), self.anchor.max(self.end));
+        let (start, end) = match self.kind {
+            Kind::Simple => (start, end),
+            Kind::Word => (word_start(grid, start), word_end(grid, end)),
+            Kind::Line => (line_start(grid, start), line_end(grid, end)),
+            Kind::Block => {
+                let (left, right) = (start.col.min(end.col), start.col.max(end.col));
+                (Point { col: left, ..start }, Point { col: right, ..end })
+            }
+        };
+        Some(SelectionRange { start, end, block: self.kind == Kind::Block })
+    }
+}
+
+impl SelectionRange {
+    pub fn contains(&self, point: Point) -> bool {
+        if self.block {
+            (self.start.line..=self.end.line).contains(&point.line)
+                && (self.start.col..=self.end.col).contains(&point.col)
+        } else {
+            self.start <= point && point <= self.end
+        }
+    }
+
+    /// Selected characters, rows end with a newline unless they wrap and trailing blanks
+    /// of each line are left out. Every row of a block is a line of its own
+    pub fn text(&self, grid: &Grid) -> String {
+        let mut text = String::new();
+        for line in self.start.line..=self.end.line {
+            let row = match grid.line(line) {
+                Some(row) => row,
+                None => continue,
+            };
+            let first = if line == self.start.line || self.block { self.start.col } else { 0 };
+            let last =
+                if line == self.end.line || self.block { self.end.col } else { row.cells.len() };
+            let cells = row.cells.iter().take(last + 1).skip(first);
+            let chars: String = cells
+                .filter(|cell| !cell.flags.contains(Flags::WIDE_SPACER))
+                .map(Cell::text)
+                .collect();
+
+            if row.wrapped && line != self.end.line && !self.block {
+                text.push_str(&chars);
+            } else {
+                text.push_str(chars.trim_end());
+                if line != self.end.line {
+                    text.push('\n');
+                }
+            }
+        }
+        text
+    }
+}
+
+fn is_word(grid: &Grid, point: Point) -> bool {
+    let cell = match grid.line(point.line).and_then(|row| row.cells.get(point.col)) {
+        Some(cell) => cell,
+        None => return false,
+    };
+    // The second half of a wide character belongs to the word of the first
+    cell.flags.contains(Flags::WIDE_SPACER)
+        || !(cell.c.is_whitespace() || WORD_SEPARATORS.contains(cell.c))
+}
+
+/// Cell before `point`, going back to the end of the previous row if it wraps
+fn previous(grid: &Grid, point: Point) -> Option<Point> {
+    if point.col > 0 {
+        return Some(Point { col: point.col - 1, ..point });
+    }
+    let line = point.line.checked_sub(1)?;
+    grid.line(line).filter(|row| row.wrapped).map(|_| Point { line, col: grid.cols() - 1 })
+}
+
+/// Cell after `point`, going on to the start of the next row if this one wraps
+fn next(grid: &Grid, point: Point) -> Option<Point> {
+    if point.col + 1 < grid.cols() {
+        return Some(Point { col: point.col + 1, ..point });
+    }
+    grid.line(point.line).filter(|row| row.wrapped).map(|_| Point { line: point.line + 1, col: 0 })
+}
+
+fn word_start(grid: &Grid, mut point: Point) -> Point {
+    if !is_word(grid, point) {
+        return point;
+    }
+    while let Some(previous) = previous(grid, point).filter(|&p| is_word(grid, p)) {
+        point = previous;
+    }
+    point
+}
+
+fn word_end(grid: &Grid, mut point: Point) -> Point {
+    if !is_word(grid, point) {
+        return point;
+    }
+    while let Some(next) = next(grid, point).filter(|&p| is_word(grid, p)) {
+        point = next;
+    }
+    point
+}
+
+/// Start of the line `point` is on, before the rows wrapped into it
+fn line_start(grid: &Grid, point: Point) -> Point {
+    let mut line = point.line;
+    while line > 0 && grid.line(line - 1).is_some_and(|row| row.wrapped) {
+        line -= 1;
+    }
+    Point { line, col: 0 }
+}
+
+/// End of the line `point` is on, after the rows it wraps into
+fn line_end(grid: &Grid, point: Point) -> Point {
+    let mut line = point.line;
+    while grid.line(line).is_some_and(|row| row.wrapped) {
+        line += 1;
+    }
+    Point { line, col: grid.cols() - 1 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::Terminal;
+
+    fn terminal(cols: usize, rows: usize, text: &str) -> Terminal {
+        let mut terminal = Terminal::new(cols, rows);
+        terminal.advance(text.replace('\n', "\r\n").as_bytes());
+        terminal
+    }
+
+    fn point(line: usize, col: usize) -> Point {
+        Point { line, col }
+    }
+
+    fn selected(terminal: &Terminal, selection: &Selection) -> String {
+        selection.range(terminal.grid()).map_or(String::new(), |r| r.text(terminal.grid()))
+    }
+
+    #[test]
+    fn drag_selects_across_rows() {
+        let terminal = terminal(10, 3, "first   \nsecond\nthird");
+        let mut selection = Selection::new(Kind::Simple, point(0, 2));
+        assert_eq!(selection.range(terminal.grid()), None);
+
+        selection.extend(point(2, 1));
+        assert_eq!(selected(&terminal, &selection), "rst\nsecond\nth");
+
+        // Dragging backwards selects the same cells
+        let mut selection = Selection::new(Kind::Simple, point(2, 1));
+        selection.extend(point(0, 2));
+        let range = selection.range(terminal.grid()).unwrap();
+        assert!(range.contains(point(1, 9)));
+        assert!(!range.contains(point(2, 2)));
+        assert_eq!(range.text(terminal.grid()), "rst\nsecond\nth");
+    }
+
+    #[test]
+    fn double_click_selects_a_word() {
+        let terminal = terminal(20, 2, "ls -l (some_dir) x");
+        let selection = Selection::new(Kind::Word, point(0, 9));
+        assert_eq!(selected(&terminal, &selection), "some_dir");
+
+        let mut selection = Selection::new(Kind::Word, point(0, 3));
+        selection.extend(point(0, 8));
+        assert_eq!(selected(&terminal, &selection), "-l (some_dir");
+    }
+
+    #[test]
+    fn words_and_lines_continue_on_wrapped_rows() {
+        let terminal = terminal(6, 4, "one twothree\nnext");
+        let selection = Selection::new(Kind::Word, point(1, 2));
+        assert_eq!(selected(&terminal, &selection), "twothree");
+
+        let selection = Selection::new(Kind::Line, point(1, 4));
+        assert_eq!(selected(&terminal, &selection), "one twothree");
+
+        let mut selection = Selection::new(Kind::Line, point(0, 0));
+        selection.extend(point(2, 0));
+        assert_eq!(selected(&terminal, &selection), "one twothree\nnext");
+    }
+
+    #[test]
+    fn block_selects_columns_of_each_row() {
+        let table = terminal(16, 3, "PID   CMD\n  1   init   x\n 42   sh");
+        let mut selection = Selection::new(Kind::Block, point(2, 6));
+        assert_eq!(selection.range(table.grid()), None);
+
+        selection.extend(point(0, 10));
+        let range = selection.range(table.grid()).unwrap();
+        assert!(range.contains(point(1, 8)));
+        assert!(!range.contains(point(1, 2)));
+        assert!(!range.contains(point(1, 11)));
+        assert_eq!(range.text(table.grid()), "CMD\ninit\nsh");
+
+        // Rows that wrap still end their line of the block
+        let wrapped = terminal(4, 3, "abcdef\ngh");
+        let mut selection = Selection::new(Kind::Block, point(0, 1));
+        selection.extend(point(2, 2));
+        assert_eq!(selected(&wrapped, &selection), "bc\nf\nh");
+    }
+
+    #[test]
+    fn selection_reaches_into_history() {
+        let mut terminal = terminal(10, 2, "a\nb\nc\nd");
+        let grid = terminal.grid();
+        assert_eq!(grid.visible_to_absolute(0), 2);
+
+        terminal.scroll_display(2);
+        let grid = terminal.grid();
+        assert_eq!(grid.visible_to_absolute(0), 0);
+        let mut selection = Selection::new(Kind::Line, point(grid.visible_to_absolute(1), 0));
+        selection.extend(point(3, 0));
+        assert_eq!(selected(&terminal, &selection), "b\nc\nd");
+    }
+}
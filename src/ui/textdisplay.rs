@@ -1,56 +1,29 @@
 use anyhow::Ok;
+use unicode_width::UnicodeWidthChar;
 
-use std::str;
+use crate::{
+    config::Config,
+    graphics::rendertext::{GlyphCell, RenderText, MARKS_PER_CELL},
+    resources::Resources,
+    terminal::grid::{Cell, Flags, Grid},
+};
 
-use crate::{graphics::rendertext::RenderText, resources::Resources};
+use super::{
+    palette::Palette,
+    selection::{Point, SelectionRange},
+};
 
-#[derive(Debug, Default)]
-pub struct Buffer {
-    content: Vec<String>,
-    input_size: usize,
-}
-
-impl Buffer {
-    pub fn new(mut history: Vec<String>, input: &str) -> Buffer {
-        if let Some(last) = history.last_mut() {
-            last.push_str(input);
-        } else {
-            history.push(input.to_string());
-        }
-        Buffer { content: history, input_size: input.len() }
-    }
-
-    fn fit_in_screen(&self, line_width: usize) -> Vec<String> {
-        if line_width == 0 {
-            return self.content.clone();
-        }
-
-        self.content
-            .iter()
-            .flat_map(|el| {
-                if el.is_empty() {
-                    return vec![String::new()];
-                }
-                el.as_bytes()
-                    .chunks(line_width)
-                    .map(|chunk| str::from_utf8(chunk).unwrap().to_string())
-                    .collect()
-            })
-            .collect()
-    }
-
-    pub fn content(&self) -> &Vec<String> {
-        &self.content
-    }
-
-    pub fn history(&self) -> &[String] {
-        &self.content[0..self.input_size]
-    }
-}
+/// Color of messages shown over the text, like config errors
+const MESSAGE_COLOR: (u8, u8, u8, u8) = (255, 85, 85, 255);
 
 pub struct TextDisplay {
     rendertext: RenderText,
+    palette: Palette,
+    pub padding: u32,
+    /// Shown over the bottom lines until it is cleared
+    message: Option<String>,
     pub line_height: u32,
+    pub glyph_width: u32,
     line_width: usize,
     lines_count: usize,
     pub lines_to_display: usize,
@@ -62,40 +35,117 @@ impl TextDisplay {
         gl: &gl::Gl,
         width: u32,
         height: u32,
-        color: (u8, u8, u8, u8),
+        config: &Config,
     ) -> Result<TextDisplay, anyhow::Error> {
-        let rendertext = RenderText::new(res, gl, width, height, color)?;
+        let padding = config.window.padding;
+        let rendertext = RenderText::new(res, gl, width, height, &config.font, padding)?;
         let line_height = rendertext.glyph_height;
-        let line_width = (width / rendertext.glyph_width) as usize;
-        let lines_to_display = (height / line_height) as usize;
-
-        Ok(TextDisplay { rendertext, line_height, line_width, lines_count: 0, lines_to_display })
+        let glyph_width = rendertext.glyph_width;
+
+        let mut textdisplay = TextDisplay {
+            rendertext,
+            palette: Palette::new(&config.colors),
+            padding,
+            message: None,
+            line_height,
+            glyph_width,
+            line_width: 0,
+            lines_count: 0,
+            lines_to_display: 0,
+        };
+        textdisplay.update_size(width as i32, height as i32);
+        Ok(textdisplay)
     }
 
     pub fn update_size(&mut self, width: i32, height: i32) {
         self.rendertext.update_size(width, height);
-        self.line_width = (width as f32 / self.rendertext.glyph_width as f32).round() as usize;
-        self.lines_to_display = (height as f32 / self.rendertext.glyph_height as f32).round() as usize;
+        let width = (width as u32).saturating_sub(2 * self.padding);
+        let height = (height as u32).saturating_sub(2 * self.padding);
+        self.line_width = (width / self.rendertext.glyph_width) as usize;
+        self.lines_to_display = (height / self.rendertext.glyph_height) as usize;
     }
 
-    pub fn update(&mut self, buffer: &Buffer) {
-        // println!("buffer: {:#?}", buffer);
+    pub fn set_message(&mut self, message: Option<String>) {
+        self.message = message;
+    }
 
-        let mut lines = buffer.fit_in_screen(self.line_width);
-        // if let Some(last) = buffer.content().last() {
-        //     if last.is_empty() {
-        //         lines.push(String::new());
-        //     }
-        // }
-        // println!("lines: {:#?}", lines);
+    /// Takes the cells shown in the view of `grid`, the selected ones are drawn inverted
+    pub fn update(&mut self, grid: &Grid, selection: Option<&SelectionRange>) {
+        let cols = grid.cols();
+        let mut cells: Vec<GlyphCell> = Vec::with_capacity(cols * grid.rows());
+        for row in 0..grid.rows() {
+            let line = grid.visible_to_absolute(row);
+            for (col, cell) in grid.visible_row(row).cells.iter().enumerate() {
+                let mut glyph = self.glyph_cell(cell);
+                if selection.is_some_and(|selection| selection.contains(Point { line, col })) {
+                    let fg = glyph.bg.unwrap_or(self.palette.background.rgba());
+                    glyph.bg = Some(glyph.fg);
+                    glyph.fg = fg;
+                }
+                cells.push(glyph);
+            }
+        }
+
+        // Show how far back the view is while it is scrolled up
+        if grid.display_offset() > 0 {
+            let indicator = format!("[{}/{}]", grid.display_offset(), grid.history().len());
+            let start = cols.saturating_sub(indicator.chars().count());
+            for (cell, c) in cells[start..cols].iter_mut().zip(indicator.chars()) {
+                *cell = GlyphCell {
+                    c,
+                    marks: [None; MARKS_PER_CELL],
+                    fg: self.palette.foreground.rgba(),
+                    bg: None,
+                    bold: false,
+                };
+            }
+        }
 
-        // Scrolling to bottom
-        if lines.len() > self.lines_to_display {
-            lines = lines[lines.len() - self.lines_to_display..].to_vec();
+        if let Some(message) = &self.message {
+            let lines: Vec<Vec<char>> = message
+                .lines()
+                .flat_map(|line| {
+                    let chars: Vec<char> = line.chars().collect();
+                    chars.chunks(cols.max(1)).map(|chunk| chunk.to_vec()).collect::<Vec<_>>()
+                })
+                .collect();
+            let shown = lines.len().min(grid.rows());
+            let first_row = grid.rows() - shown;
+            for (row, line) in lines.iter().take(shown).enumerate() {
+                let start = (first_row + row) * cols;
+                for (col, cell) in cells[start..start + cols].iter_mut().enumerate() {
+                    let c = line.get(col).copied().unwrap_or(' ');
+                    *cell = GlyphCell {
+                        c,
+                        marks: [None; MARKS_PER_CELL],
+                        fg: MESSAGE_COLOR,
+                        bg: Some(self.palette.background.rgba()),
+                        bold: false,
+                    };
+                }
+            }
+        }
+
+        self.lines_count = grid.rows();
+        self.rendertext.update(cols, &cells);
+    }
+
+    fn glyph_cell(&self, cell: &Cell) -> GlyphCell {
+        // The second half of a wide character is covered by the glyph before it
+        let c = if cell.flags.contains(Flags::WIDE_SPACER) { ' ' } else { cell.c };
+        let mut marks = [None; MARKS_PER_CELL];
+        let zerowidth = cell.zerowidth.iter().filter(|c| c.width() == Some(0));
+        for (mark, &c) in marks.iter_mut().zip(zerowidth) {
+            *mark = Some(c);
+        }
+        let (fg, bg) = self.palette.cell_colors(cell);
+        GlyphCell {
+            c,
+            marks,
+            fg: fg.rgba(),
+            bg: bg.map(|bg| bg.rgba()),
+            bold: cell.flags.contains(Flags::BOLD),
         }
-        self.lines_count = lines.len();
-        self.rendertext.update(lines.as_slice());
-        // println!("{:#?}", buffer);
     }
 
     pub fn get_line_width(&self) -> usize {